@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+use crate::error::PoetryWallError;
+
+/// How the lines line up with each other.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Alignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Alignment {
+    /// How far to move a line of `width` in from the left of a block that's
+    /// `block_width` wide.
+    pub fn offset(self, width: f32, block_width: f32) -> f32 {
+        match self {
+            Alignment::Left => 0.0,
+            Alignment::Center => (block_width - width) / 2.0,
+            Alignment::Right => block_width - width,
+        }
+    }
+}
+
+impl FromStr for Alignment {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "left" => Ok(Alignment::Left),
+            "center" | "centre" => Ok(Alignment::Center),
+            "right" => Ok(Alignment::Right),
+            _ => Err(PoetryWallError::InvalidMissingOption(format!("align: {}", s))),
+        }
+    }
+}
+
+/// Punctuation that hangs into the left margin when it starts a line.
+pub fn hangs_at_start(c: char) -> bool {
+    "\"'“‘«‹„‚-–—".contains(c)
+}
+
+/// Punctuation that hangs into the right margin when it ends a line.
+pub fn hangs_at_end(c: char) -> bool {
+    "\"'”’»›“‘-–—.,;:!?…".contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use spectral::prelude::*;
+
+    use crate::alignment::{hangs_at_end, hangs_at_start, Alignment};
+
+    #[test]
+    fn reads_from_string() {
+        assert_that(&Alignment::from_str("Center")).is_ok().is_equal_to(Alignment::Center);
+        assert_that(&Alignment::from_str("justify")).is_err();
+    }
+
+    #[test]
+    fn hangs_punctuation() {
+        assert_that(&hangs_at_start('“')).is_true();
+        assert_that(&hangs_at_start('.')).is_false();
+        assert_that(&hangs_at_end('.')).is_true();
+        assert_that(&hangs_at_end('I')).is_false();
+    }
+}
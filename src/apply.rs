@@ -0,0 +1,307 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::dimension::Dimension;
+use crate::error::{PoetryWallError, Result};
+
+/// The desktops whose wallpaper can be set without help.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Desktop {
+    /// GNOME and its relatives, through `gsettings`.
+    Gnome,
+    /// KDE Plasma, through `plasma-apply-wallpaperimage`.
+    Plasma,
+    /// sway, which draws each output's background with swaybg.
+    Sway,
+    /// Hyprland, with hyprpaper.
+    Hyprland,
+    /// Any other X11 window manager, through feh.
+    X11,
+}
+
+impl Desktop {
+    /// Works out the desktop from the session's environment variables. The
+    /// compositors come first, since they often claim to be GNOME too.
+    pub fn detect<F: Fn(&str) -> Option<String>>(var: F) -> Option<Self> {
+        let current = var("XDG_CURRENT_DESKTOP").unwrap_or_default().to_lowercase();
+        let current: Vec<&str> = current.split(':').collect();
+        if var("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            Some(Desktop::Hyprland)
+        } else if var("SWAYSOCK").is_some() {
+            Some(Desktop::Sway)
+        } else if current.iter().any(|name| ["gnome", "unity", "budgie", "pantheon"].contains(name)) {
+            Some(Desktop::Gnome)
+        } else if current.contains(&"kde") {
+            Some(Desktop::Plasma)
+        } else if var("DISPLAY").is_some() {
+            Some(Desktop::X11)
+        } else {
+            None
+        }
+    }
+}
+
+/// A screen, as the desktop names it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Monitor {
+    pub name: String,
+    pub dimensions: Dimension,
+}
+
+/// Sets the images as the wallpaper, each on the monitors of its size. With a
+/// command template, that's run with `{file}`, `{width}` and `{height}` filled
+/// in from the first image, instead of working out the desktop.
+pub fn apply_wallpaper(images: &[(Dimension, PathBuf)], command: Option<&str>) -> Result<()> {
+    let images = images
+        .iter()
+        .map(|(dimensions, file)| Ok((*dimensions, file.canonicalize()?)))
+        .collect::<Result<Vec<_>>>()?;
+    let commands = match command {
+        Some(template) => vec![custom_command(template, &images)?],
+        None => {
+            let desktop = Desktop::detect(|name| env::var(name).ok()).ok_or_else(|| {
+                PoetryWallError::ApplyError(String::from(
+                    "this desktop isn't one it knows, so give it an --apply-command",
+                ))
+            })?;
+            wallpaper_commands(desktop, &list_monitors(desktop), &images)
+        }
+    };
+    for command in commands {
+        run(&command)?;
+    }
+    Ok(())
+}
+
+/// The commands that set the wallpaper on a desktop. Monitors get the image
+/// of their size, or else the first one. Without any monitors, the first
+/// image goes on all of them.
+pub fn wallpaper_commands(desktop: Desktop, monitors: &[Monitor], images: &[(Dimension, PathBuf)]) -> Vec<Vec<String>> {
+    let first = match images.first() {
+        Some((_, file)) => file.display().to_string(),
+        None => return Vec::new(),
+    };
+    let image_for = |monitor: &Monitor| {
+        images
+            .iter()
+            .find(|(dimensions, _)| *dimensions == monitor.dimensions)
+            .map_or_else(|| first.clone(), |(_, file)| file.display().to_string())
+    };
+    let command = |args: &[&str]| args.iter().map(|arg| String::from(*arg)).collect::<Vec<_>>();
+
+    match desktop {
+        Desktop::Gnome => {
+            // GNOME shows one image across every monitor, so it gets the biggest.
+            let (_, file) = images.iter().max_by_key(|(dimensions, _)| dimensions.width * dimensions.height).unwrap();
+            let uri = format!("file://{}", file.display());
+            vec![
+                command(&["gsettings", "set", "org.gnome.desktop.background", "picture-uri", &uri]),
+                command(&["gsettings", "set", "org.gnome.desktop.background", "picture-uri-dark", &uri]),
+                command(&["gsettings", "set", "org.gnome.desktop.background", "picture-options", "zoom"]),
+            ]
+        }
+        Desktop::Plasma => vec![command(&["plasma-apply-wallpaperimage", &first])],
+        Desktop::Sway if monitors.is_empty() => vec![command(&["swaymsg", "output", "*", "bg", &first, "fill"])],
+        Desktop::Sway => monitors
+            .iter()
+            .map(|monitor| command(&["swaymsg", "output", &monitor.name, "bg", &image_for(monitor), "fill"]))
+            .collect(),
+        Desktop::Hyprland => {
+            let mut files: Vec<String> = monitors.iter().map(image_for).collect();
+            files.push(first.clone());
+            files.sort();
+            files.dedup();
+            let preloads = files.iter().map(|file| command(&["hyprctl", "hyprpaper", "preload", file]));
+            let wallpapers: Vec<Vec<String>> = if monitors.is_empty() {
+                vec![command(&["hyprctl", "hyprpaper", "wallpaper", &format!(",{}", first)])]
+            } else {
+                monitors
+                    .iter()
+                    .map(|monitor| {
+                        let wallpaper = format!("{},{}", monitor.name, image_for(monitor));
+                        command(&["hyprctl", "hyprpaper", "wallpaper", &wallpaper])
+                    })
+                    .collect()
+            };
+            preloads.chain(wallpapers).collect()
+        }
+        Desktop::X11 => {
+            // feh gives each screen the next file, in the order xrandr lists them.
+            let mut args = vec![String::from("feh"), String::from("--bg-fill")];
+            if monitors.is_empty() {
+                args.push(first);
+            } else {
+                args.extend(monitors.iter().map(image_for));
+            }
+            vec![args]
+        }
+    }
+}
+
+/// The user's command, run by the shell, with the first image's file quoted.
+fn custom_command(template: &str, images: &[(Dimension, PathBuf)]) -> Result<Vec<String>> {
+    let (dimensions, file) = images
+        .first()
+        .ok_or_else(|| PoetryWallError::ApplyError(String::from("there's no image to apply")))?;
+    let script = template
+        .replace("{file}", &shell_quote(file))
+        .replace("{width}", &dimensions.width.to_string())
+        .replace("{height}", &dimensions.height.to_string());
+    Ok(vec![String::from("sh"), String::from("-c"), script])
+}
+
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', "'\\''"))
+}
+
+/// The monitors, for the desktops that can have a different wallpaper on
+/// each. If they can't be listed, there are none.
+fn list_monitors(desktop: Desktop) -> Vec<Monitor> {
+    let output = |program: &str, args: &[&str]| {
+        Command::new(program)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+            .unwrap_or_default()
+    };
+    match desktop {
+        Desktop::Sway => parse_sway_outputs(&output("swaymsg", &["-t", "get_outputs", "-r"])),
+        Desktop::Hyprland => parse_hyprland_monitors(&output("hyprctl", &["monitors", "-j"])),
+        Desktop::X11 => parse_xrandr_monitors(&output("xrandr", &["--listactivemonitors"])),
+        Desktop::Gnome | Desktop::Plasma => Vec::new(),
+    }
+}
+
+#[derive(Deserialize)]
+struct SwayOutput {
+    name: String,
+    active: bool,
+    current_mode: Option<SwayMode>,
+}
+
+#[derive(Deserialize)]
+struct SwayMode {
+    width: u32,
+    height: u32,
+}
+
+fn parse_sway_outputs(json: &str) -> Vec<Monitor> {
+    let outputs: Vec<SwayOutput> = serde_json::from_str(json).unwrap_or_default();
+    outputs
+        .into_iter()
+        .filter(|output| output.active)
+        .filter_map(|output| {
+            let mode = output.current_mode?;
+            Some(Monitor { name: output.name, dimensions: Dimension::new(mode.width, mode.height) })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct HyprlandMonitor {
+    name: String,
+    width: u32,
+    height: u32,
+}
+
+fn parse_hyprland_monitors(json: &str) -> Vec<Monitor> {
+    let monitors: Vec<HyprlandMonitor> = serde_json::from_str(json).unwrap_or_default();
+    monitors
+        .into_iter()
+        .map(|monitor| Monitor { name: monitor.name, dimensions: Dimension::new(monitor.width, monitor.height) })
+        .collect()
+}
+
+/// Reads lines like ` 0: +*DP-1 2560/597x1440/336+0+0  DP-1`, where the
+/// sizes are in pixels and millimeters.
+fn parse_xrandr_monitors(text: &str) -> Vec<Monitor> {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (geometry, name) = (fields.get(2)?, fields.last()?);
+            let (width, rest) = geometry.split_once('/')?;
+            let height = rest.split_once('x')?.1.split_once('/')?.0;
+            let dimensions = Dimension::new(width.parse().ok()?, height.parse().ok()?);
+            Some(Monitor { name: String::from(*name), dimensions })
+        })
+        .collect()
+}
+
+fn run(command: &[String]) -> Result<()> {
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .status()
+        .map_err(|err| PoetryWallError::ApplyError(format!("{}: {}", command[0], err)))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(PoetryWallError::ApplyError(format!("{} failed with {}", command.join(" "), status)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use spectral::prelude::*;
+
+    use crate::apply::{custom_command, parse_xrandr_monitors, wallpaper_commands, Desktop, Monitor};
+    use crate::dimension::Dimension;
+
+    fn images() -> Vec<(Dimension, PathBuf)> {
+        vec![
+            (Dimension::new(1920, 1080), PathBuf::from("/walls/wall-1920x1080.png")),
+            (Dimension::new(2560, 1440), PathBuf::from("/walls/wall-2560x1440.png")),
+        ]
+    }
+
+    #[test]
+    fn detects_the_desktop() {
+        let detect = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> =
+                vars.iter().map(|&(name, value)| (String::from(name), String::from(value))).collect();
+            Desktop::detect(|name| vars.get(name).cloned())
+        };
+        assert_that(&detect(&[("XDG_CURRENT_DESKTOP", "ubuntu:GNOME")])).is_equal_to(Some(Desktop::Gnome));
+        assert_that(&detect(&[("XDG_CURRENT_DESKTOP", "KDE")])).is_equal_to(Some(Desktop::Plasma));
+        assert_that(&detect(&[("XDG_CURRENT_DESKTOP", "sway"), ("SWAYSOCK", "/run/sway.sock")]))
+            .is_equal_to(Some(Desktop::Sway));
+        assert_that(&detect(&[("DISPLAY", ":0")])).is_equal_to(Some(Desktop::X11));
+        assert_that(&detect(&[])).is_none();
+    }
+
+    #[test]
+    fn gives_each_monitor_its_size() {
+        let monitors = parse_xrandr_monitors(
+            "Monitors: 2\n 0: +*DP-1 2560/597x1440/336+0+0  DP-1\n 1: +HDMI-1 1920/510x1080/287+2560+0  HDMI-1\n",
+        );
+        assert_that(&monitors).is_equal_to(vec![
+            Monitor { name: String::from("DP-1"), dimensions: Dimension::new(2560, 1440) },
+            Monitor { name: String::from("HDMI-1"), dimensions: Dimension::new(1920, 1080) },
+        ]);
+
+        let feh = wallpaper_commands(Desktop::X11, &monitors, &images());
+        assert_that(&feh[0][2..].to_vec()).is_equal_to(vec![
+            String::from("/walls/wall-2560x1440.png"),
+            String::from("/walls/wall-1920x1080.png"),
+        ]);
+        let sway = wallpaper_commands(Desktop::Sway, &monitors, &images());
+        assert_that(&sway[1].join(" ")).is_equal_to(String::from("swaymsg output HDMI-1 bg /walls/wall-1920x1080.png fill"));
+        let gnome = wallpaper_commands(Desktop::Gnome, &[], &images());
+        assert_that(&gnome[0][4]).is_equal_to(String::from("file:///walls/wall-2560x1440.png"));
+    }
+
+    #[test]
+    fn fills_in_the_command_template() {
+        let images = vec![(Dimension::new(1920, 1080), PathBuf::from("/walls/it's.png"))];
+        let command = custom_command("swww img {file} # {width}x{height}", &images).unwrap();
+        assert_that(&command[2]).is_equal_to(String::from("swww img '/walls/it'\\''s.png' # 1920x1080"));
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::dimension::Dimension;
+use crate::error::{PoetryWallError, Result};
+use crate::image_utils::ImageFormat;
+use crate::naming::{sized_file_name, DEFAULT_OUTPUT_TEMPLATE};
+use crate::options::{PoetryWallOptions, Sink, Source};
+use crate::pagination::page_file_name;
+use crate::{load_poetry_wall, write_poetry_wall};
+
+/// One poem at one size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchJob {
+    pub poem_file: PathBuf,
+    pub dimensions: Dimension,
+    pub output_file: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum BatchOutcome {
+    Rendered,
+    /// The image was newer than the poem and font, so it was left alone.
+    UpToDate,
+    Failed(PoetryWallError),
+}
+
+/// What happened to each job in a batch.
+#[derive(Debug)]
+pub struct BatchReport {
+    pub results: Vec<(BatchJob, BatchOutcome)>,
+}
+
+impl BatchReport {
+    pub fn failures(&self) -> impl Iterator<Item = (&BatchJob, &PoetryWallError)> {
+        self.results.iter().filter_map(|(job, outcome)| match outcome {
+            BatchOutcome::Failed(err) => Some((job, err)),
+            _ => None,
+        })
+    }
+
+    fn count(&self, matches: fn(&BatchOutcome) -> bool) -> usize {
+        self.results.iter().filter(|(_, outcome)| matches(outcome)).count()
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} rendered, {} up to date, {} failed",
+            self.count(|outcome| matches!(outcome, BatchOutcome::Rendered)),
+            self.count(|outcome| matches!(outcome, BatchOutcome::UpToDate)),
+            self.count(|outcome| matches!(outcome, BatchOutcome::Failed(_))),
+        )
+    }
+}
+
+/// The poems named by each of the arguments, which can be files, directories
+/// of markdown files, or glob patterns.
+pub fn find_poems<S: AsRef<str>>(patterns: &[S]) -> Result<Vec<PathBuf>> {
+    let mut poems = Vec::new();
+    for pattern in patterns.iter().map(AsRef::as_ref) {
+        if Path::new(pattern).is_dir() {
+            for entry in fs::read_dir(pattern)? {
+                let path = entry?.path();
+                if path.is_file() && path.extension().is_some_and(|extension| extension == "md") {
+                    poems.push(path);
+                }
+            }
+        } else {
+            let paths = glob::glob(pattern)
+                .map_err(|err| PoetryWallError::InvalidMissingOption(format!("poems: {}: {}", pattern, err)))?;
+            let count = poems.len();
+            poems.extend(paths.filter_map(|path| path.ok()).filter(|path| path.is_file()));
+            if poems.len() == count {
+                return Err(PoetryWallError::InvalidMissingOption(format!("poems: nothing matches {}", pattern)));
+            }
+        }
+    }
+    poems.sort();
+    poems.dedup();
+    Ok(poems)
+}
+
+/// Every poem at every size in the options, each written to `output_dir`
+/// under the poem's name. Two poems with the same name, like `a/spring.md`
+/// and `b/spring.md`, would overwrite each other, so that's an error.
+pub fn batch_jobs(poems: &[PathBuf], output_dir: &Path, options: &PoetryWallOptions) -> Result<Vec<BatchJob>> {
+    let template = match &options.output_template {
+        Some(template) => Some(template.as_str()),
+        None if options.dimensions.len() > 1 => Some(DEFAULT_OUTPUT_TEMPLATE),
+        None => None,
+    };
+    let jobs: Vec<BatchJob> = poems
+        .iter()
+        .flat_map(|poem_file| {
+            let mut output_file = output_dir.join(poem_file.file_stem().unwrap_or_default());
+            output_file.set_extension(options.format.map(ImageFormat::extension).unwrap_or("png"));
+            options.dimensions.iter().map(move |&dimensions| BatchJob {
+                poem_file: poem_file.clone(),
+                dimensions,
+                output_file: match template {
+                    Some(template) => sized_file_name(&output_file, template, &dimensions, options.format),
+                    None => output_file.clone(),
+                },
+            })
+        })
+        .collect();
+
+    let mut poem_files = HashMap::new();
+    for job in &jobs {
+        if let Some(other) = poem_files.insert(&job.output_file, &job.poem_file) {
+            if other != &job.poem_file {
+                return Err(PoetryWallError::InvalidMissingOption(format!(
+                    "poems: {} and {} would both be written to {}",
+                    other.display(),
+                    job.poem_file.display(),
+                    job.output_file.display()
+                )));
+            }
+        }
+    }
+    Ok(jobs)
+}
+
+/// Renders the poems in parallel, skipping the jobs that are up to date
+/// unless `force` is set. A job failing doesn't stop the others.
+pub fn run_batch(jobs: Vec<BatchJob>, options: &PoetryWallOptions, force: bool) -> BatchReport {
+    let mut poems: Vec<Vec<BatchJob>> = Vec::new();
+    for job in jobs {
+        match poems.iter_mut().find(|poem_jobs| poem_jobs[0].poem_file == job.poem_file) {
+            Some(poem_jobs) => poem_jobs.push(job),
+            None => poems.push(vec![job]),
+        }
+    }
+    let results = poems
+        .into_par_iter()
+        .flat_map_iter(|jobs| render_poem(jobs, options, force))
+        .collect();
+    BatchReport { results }
+}
+
+/// Renders one poem's jobs, loading the poem and font once for all of its
+/// sizes.
+fn render_poem(jobs: Vec<BatchJob>, options: &PoetryWallOptions, force: bool) -> Vec<(BatchJob, BatchOutcome)> {
+    let (up_to_date, pending): (Vec<_>, Vec<_>) =
+        jobs.into_iter().partition(|job| !force && is_up_to_date(job, options));
+    let mut results: Vec<_> = up_to_date.into_iter().map(|job| (job, BatchOutcome::UpToDate)).collect();
+    let Some(first) = pending.first() else {
+        return results;
+    };
+
+    let mut options = options.clone();
+    options.poem = Some(Source::File(first.poem_file.clone()));
+    options.dimensions = pending.iter().map(|job| job.dimensions).collect();
+    options.output_template = None;
+    options.preview = None;
+    options.apply = false;
+    options.emit_layout = None;
+    options.layout_file = None;
+    match load_poetry_wall(&options) {
+        Ok(mut wall) => results.extend(pending.into_iter().map(|job| {
+            let plan = wall.plan(job.dimensions);
+            let images = wall.draw(&plan);
+            let output = Sink::File(job.output_file.clone());
+            let outcome = match write_poetry_wall(&plan, images, &options, Some(&output), None) {
+                Ok(()) => BatchOutcome::Rendered,
+                Err(err) => BatchOutcome::Failed(err),
+            };
+            (job, outcome)
+        })),
+        Err(err) => {
+            // Errors can't be copied, so the other sizes get its message.
+            let message = err.to_string();
+            let mut err = Some(err);
+            results.extend(pending.into_iter().map(|job| {
+                let err = err.take().unwrap_or_else(|| PoetryWallError::BatchError(message.clone()));
+                (job, BatchOutcome::Failed(err))
+            }));
+        }
+    }
+    results
+}
+
+/// Whether the image, or the first page of a series, is newer than both the
+/// poem and the font.
+fn is_up_to_date(job: &BatchJob, options: &PoetryWallOptions) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let output = modified(&job.output_file).or_else(|| modified(&page_file_name(&job.output_file, 1)));
+    let sources = [modified(&job.poem_file), modified(&options.font_file)];
+    match output {
+        Some(output) => sources.iter().flatten().all(|&source| source <= output),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+
+    use spectral::prelude::*;
+
+    use crate::batch::batch_jobs;
+    use crate::color::Color;
+    use crate::dimension::Dimension;
+    use crate::options::PoetryWallOptions;
+    use crate::units::Length;
+
+    #[test]
+    fn makes_a_job_for_each_poem_and_size() {
+        let dimensions = vec![Dimension::new(640, 480), Dimension::new(480, 640)];
+        let options = PoetryWallOptions::new(
+            None,
+            "builtin:serif",
+            Length::px(72.0),
+            Color::from_str("white").unwrap(),
+            Color::from_str("black").unwrap(),
+            dimensions,
+            None,
+            None,
+            None,
+        );
+        let poems = [PathBuf::from("poems/a.md"), PathBuf::from("poems/b.md")];
+        let jobs = batch_jobs(&poems, Path::new("walls"), &options).unwrap();
+        assert_that(&jobs.len()).is_equal_to(4);
+        assert_that(&jobs[1].output_file).is_equal_to(PathBuf::from("walls/a-480x640.png"));
+        assert_that(&jobs[2].poem_file).is_equal_to(PathBuf::from("poems/b.md"));
+
+        let same_names = [PathBuf::from("a/spring.md"), PathBuf::from("b/spring.md")];
+        assert_that(&batch_jobs(&same_names, Path::new("walls"), &options)).is_err();
+        let same_stems = [PathBuf::from("spring.md"), PathBuf::from("spring.txt")];
+        assert_that(&batch_jobs(&same_stems, Path::new("walls"), &options)).is_err();
+    }
+}
@@ -1,5 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use crate::font::GlyphVec;
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct BoundingBox {
     pub top: i32,
     pub left: i32,
@@ -8,23 +11,80 @@ pub struct BoundingBox {
 }
 
 impl BoundingBox {
+    /// A box that doesn't contain anything, not even the origin. Adding
+    /// anything to it gives that thing's box.
+    pub fn empty() -> Self {
+        BoundingBox {
+            top: i32::MAX,
+            left: i32::MAX,
+            bottom: i32::MIN,
+            right: i32::MIN,
+        }
+    }
+
+    /// The smallest box covering the ink of all of the glyphs. Glyphs without
+    /// any ink, like spaces, don't count, so this is empty if there isn't any.
     pub fn compute_bounding_box(glyphs: &GlyphVec) -> Self {
-        let mut bb = BoundingBox {
-            top: 0,
-            left: 0,
-            bottom: 0,
-            right: 0,
-        };
-
-        for glyph in glyphs {
-            if let Some(glyph_bb) = glyph.pixel_bounding_box() {
-                bb.top = bb.top.min(glyph_bb.min.y);
-                bb.left = bb.left.min(glyph_bb.min.x);
-                bb.bottom = bb.bottom.max(glyph_bb.max.y);
-                bb.right = bb.right.max(glyph_bb.max.x);
-            }
+        glyphs
+            .iter()
+            .filter_map(|glyph| glyph.pixel_bounding_box())
+            .fold(BoundingBox::empty(), BoundingBox::union)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.top > self.bottom || self.left > self.right
+    }
+
+    pub fn union(self, other: BoundingBox) -> Self {
+        BoundingBox {
+            top: self.top.min(other.top),
+            left: self.left.min(other.left),
+            bottom: self.bottom.max(other.bottom),
+            right: self.right.max(other.right),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        if self.is_empty() {
+            0
+        } else {
+            (self.right - self.left) as u32
         }
+    }
+
+    pub fn height(&self) -> u32 {
+        if self.is_empty() {
+            0
+        } else {
+            (self.bottom - self.top) as u32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ab_glyph::{point, PxScale};
+    use spectral::prelude::*;
+
+    use crate::bounding_box::BoundingBox;
+    use crate::font::{load_font, Synthesis};
+    use crate::shaping::{shape_line, FontFeatures};
+
+    #[test]
+    fn starts_empty() {
+        let bb = BoundingBox::compute_bounding_box(&Vec::new());
+        assert_that(&bb.is_empty()).is_true();
+        assert_that(&bb.width()).is_equal_to(0);
+    }
 
-        bb
+    #[test]
+    fn covers_only_the_ink() {
+        let font = load_font(&"./tests/fixtures/DejaVuSansMono.ttf").unwrap();
+        let shaped = shape_line(&font, "o", &FontFeatures::default());
+        let glyph = font.glyph(shaped[0].glyph_id, PxScale::from(48.0), point(100.0, 200.0), Synthesis::default());
+        let bb = BoundingBox::compute_bounding_box(&vec![glyph]);
+        assert_that(&bb.left).is_greater_than(100);
+        assert_that(&bb.top).is_greater_than(150);
+        assert_that(&bb.bottom).is_less_than_or_equal_to(201);
     }
 }
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use crate::error::{PoetryWallError, Result};
+use crate::font::FontStyle;
+
+/// Font names starting with this pick one of the fonts compiled into the
+/// binary instead of a file, e.g., `builtin:serif`.
+pub const BUILTIN_PREFIX: &str = "builtin:";
+
+/// The font to use when one isn't given.
+pub const DEFAULT_FONT: &str = "builtin:serif";
+
+/// A family compiled into the binary. The faces are in the order of
+/// `FontStyle`: regular, bold, italic and bold italic.
+struct BuiltinFamily {
+    name: &'static str,
+    faces: [&'static [u8]; 4],
+}
+
+/// The bundled fonts are DejaVu; see `fonts/LICENSE`.
+const FAMILIES: &[BuiltinFamily] = &[
+    BuiltinFamily {
+        name: "serif",
+        faces: [
+            include_bytes!("../fonts/DejaVuSerif.ttf"),
+            include_bytes!("../fonts/DejaVuSerif-Bold.ttf"),
+            include_bytes!("../fonts/DejaVuSerif-Italic.ttf"),
+            include_bytes!("../fonts/DejaVuSerif-BoldItalic.ttf"),
+        ],
+    },
+    BuiltinFamily {
+        name: "mono",
+        faces: [
+            include_bytes!("../fonts/DejaVuSansMono.ttf"),
+            include_bytes!("../fonts/DejaVuSansMono-Bold.ttf"),
+            include_bytes!("../fonts/DejaVuSansMono-Oblique.ttf"),
+            include_bytes!("../fonts/DejaVuSansMono-BoldOblique.ttf"),
+        ],
+    },
+];
+
+/// The names that can follow `builtin:`.
+pub fn builtin_names() -> Vec<&'static str> {
+    FAMILIES.iter().map(|family| family.name).collect()
+}
+
+/// If the font file is really a `builtin:` selector, this returns the name
+/// after the prefix.
+pub fn builtin_name(font_file: &Path) -> Option<&str> {
+    font_file.to_str()?.strip_prefix(BUILTIN_PREFIX)
+}
+
+/// The data for one face of a built-in family.
+pub fn builtin_face(name: &str, style: FontStyle) -> Result<&'static [u8]> {
+    FAMILIES
+        .iter()
+        .find(|family| family.name == name)
+        .map(|family| family.faces[style as usize])
+        .ok_or_else(|| {
+            PoetryWallError::InvalidFont(format!(
+                "there's no built-in font '{}'; try one of {}",
+                name,
+                builtin_names()
+                    .iter()
+                    .map(|name| format!("{}{}", BUILTIN_PREFIX, name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use spectral::prelude::*;
+
+    use crate::builtin::{builtin_face, builtin_name, DEFAULT_FONT};
+    use crate::font::FontStyle;
+
+    #[test]
+    fn reads_builtin_selector() {
+        assert_that(&builtin_name(Path::new(DEFAULT_FONT))).is_some().is_equal_to("serif");
+        assert_that(&builtin_name(Path::new("./tests/fixtures/DejaVuSansMono.ttf"))).is_none();
+    }
+
+    #[test]
+    fn fails_on_unknown_builtin() {
+        assert_that(&builtin_face("fraktur", FontStyle::Regular)).is_err();
+    }
+}
@@ -5,7 +5,7 @@ use palette::Srgb;
 
 use crate::error::PoetryWallError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Color(Srgb<u8>);
 
 impl Color {
@@ -30,7 +30,7 @@ impl Color {
 
     pub fn as_array(&self) -> [u8; 4] {
         let color = self.srgb();
-        [color.red as u8, color.green, color.blue, 255]
+        [color.red, color.green, color.blue, 255]
     }
 }
 
@@ -0,0 +1,51 @@
+use std::str::FromStr;
+
+use crate::error::PoetryWallError;
+
+/// The most columns `auto` will try.
+pub const MAX_AUTO_COLUMNS: usize = 4;
+
+/// How many columns to set the poem in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Columns {
+    Count(usize),
+    /// Whichever number of columns lets the type be the biggest.
+    Auto,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns::Count(1)
+    }
+}
+
+impl FromStr for Columns {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "auto" {
+            return Ok(Columns::Auto);
+        }
+        match s.parse() {
+            Ok(count) if count > 0 => Ok(Columns::Count(count)),
+            _ => Err(PoetryWallError::InvalidMissingOption(format!("columns: {}", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use spectral::prelude::*;
+
+    use crate::columns::Columns;
+
+    #[test]
+    fn reads_from_string() {
+        assert_that(&Columns::from_str("auto")).is_ok().is_equal_to(Columns::Auto);
+        assert_that(&Columns::from_str("3")).is_ok().is_equal_to(Columns::Count(3));
+        assert_that(&Columns::from_str("0")).is_err();
+    }
+}
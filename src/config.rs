@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+
+use crate::error::{PoetryWallError, Result};
+
+/// The name of the configuration file, both in the current directory and in
+/// `poetry-wall/` in the user's config directory.
+pub const CONFIG_FILE_NAME: &str = "poetry-wall.toml";
+
+/// Settings from configuration files, keyed by the long names of the
+/// command line options. Settings at the top of a file apply to every run,
+/// and ones in a `[profiles.NAME]` table only with `--profile NAME`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    settings: BTreeMap<String, Value>,
+    profiles: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+impl Config {
+    /// Reads the user's configuration file, and then the one in the current
+    /// directory on top of it. Either can be missing.
+    pub fn load() -> Result<Self> {
+        let mut config = Config::default();
+        for path in Config::paths() {
+            if path.is_file() {
+                config.merge(Config::from_file(&path)?);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Where the configuration files are read from, in order.
+    pub fn paths() -> Vec<PathBuf> {
+        let user_file = dirs::config_dir().map(|dir| dir.join("poetry-wall").join(CONFIG_FILE_NAME));
+        user_file.into_iter().chain(Some(PathBuf::from(CONFIG_FILE_NAME))).collect()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Config::from_toml(&fs::read_to_string(path)?)
+            .map_err(|err| PoetryWallError::ConfigError(format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self> {
+        let table: BTreeMap<String, Value> =
+            toml::from_str(s).map_err(|err| PoetryWallError::ConfigError(err.to_string()))?;
+        let mut config = Config::default();
+        for (key, value) in table {
+            match value {
+                Value::Table(profiles) if key == "profiles" => {
+                    for (name, profile) in profiles {
+                        let profile = match profile {
+                            Value::Table(profile) => {
+                                for (key, value) in &profile {
+                                    check_setting(key, value)?;
+                                }
+                                profile.into_iter().collect()
+                            }
+                            _ => return Err(PoetryWallError::ConfigError(format!("profile {} isn't a table", name))),
+                        };
+                        config.profiles.insert(name, profile);
+                    }
+                }
+                value => {
+                    check_setting(&key, &value)?;
+                    config.settings.insert(key, value);
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Adds the settings and profiles from `other`, which win over these.
+    pub fn merge(&mut self, other: Config) {
+        self.settings.extend(other.settings);
+        for (name, profile) in other.profiles {
+            self.profiles.entry(name).or_default().extend(profile);
+        }
+    }
+
+    /// The settings with the profile's laid over them. Booleans are `true` or
+    /// `false`, and everything else is as it would be on the command line.
+    pub fn settings(&self, profile: Option<&str>) -> Result<BTreeMap<String, String>> {
+        let mut settings = self.settings.clone();
+        if let Some(name) = profile {
+            let profile = self
+                .profiles
+                .get(name)
+                .ok_or_else(|| PoetryWallError::ConfigError(format!("no profile named {}", name)))?;
+            settings.extend(profile.clone());
+        }
+        Ok(settings
+            .into_iter()
+            .map(|(key, value)| match value {
+                Value::String(s) => (key, s),
+                value => (key, value.to_string()),
+            })
+            .collect())
+    }
+}
+
+/// Checks that a setting is something that could be given on the command
+/// line.
+fn check_setting(key: &str, value: &Value) -> Result<()> {
+    match value {
+        Value::String(_) | Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => Ok(()),
+        _ => Err(PoetryWallError::ConfigError(format!("{} should be a string, number or boolean", key))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use crate::config::Config;
+
+    const CONFIG: &str = r#"
+font = "fonts/Gentium.ttf"
+max-font-size = 64
+page-numbers = true
+
+[profiles.night-phone]
+dimensions = "phones"
+max-font-size = "5%"
+"#;
+
+    #[test]
+    fn lays_profiles_over_settings() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        let settings = config.settings(None).unwrap();
+        assert_that(&settings.get("max-font-size")).is_equal_to(Some(&String::from("64")));
+        assert_that(&settings.get("page-numbers")).is_equal_to(Some(&String::from("true")));
+
+        let settings = config.settings(Some("night-phone")).unwrap();
+        assert_that(&settings.get("max-font-size")).is_equal_to(Some(&String::from("5%")));
+        assert_that(&settings.get("font")).is_equal_to(Some(&String::from("fonts/Gentium.ttf")));
+        assert_that(&config.settings(Some("desk-4k"))).is_err();
+    }
+
+    #[test]
+    fn merges_files() {
+        let mut config = Config::from_toml(CONFIG).unwrap();
+        config.merge(Config::from_toml("[profiles.night-phone]\ncolor = \"gray\"\n").unwrap());
+        let settings = config.settings(Some("night-phone")).unwrap();
+        assert_that(&settings.get("color")).is_equal_to(Some(&String::from("gray")));
+        assert_that(&settings.get("dimensions")).is_equal_to(Some(&String::from("phones")));
+    }
+
+    #[test]
+    fn rejects_nested_settings() {
+        assert_that(&Config::from_toml("[colors]\ntext = \"white\"\n")).is_err();
+        assert_that(&Config::from_toml("[profiles.phone]\nsizes = [1, 2]\n")).is_err();
+    }
+}
@@ -1,8 +1,16 @@
 use std::str::FromStr;
 
-use crate::error::PoetryWallError;
+use crate::error::{PoetryWallError, Result};
+use crate::units::{Length, Unit, DEFAULT_DPI};
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
+/// Named groups of common screen sizes.
+pub const PRESETS: &[(&str, &[(u32, u32)])] = &[
+    ("phones", &[(1080, 2400), (1170, 2532), (1179, 2556), (1290, 2796), (1440, 3200)]),
+    ("tablets", &[(1620, 2160), (1640, 2360), (2048, 2732), (1600, 2560)]),
+    ("desktops", &[(1920, 1080), (2560, 1440), (3440, 1440), (3840, 2160)]),
+];
+
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Dimension {
     pub width: u32,
     pub height: u32,
@@ -12,18 +20,55 @@ impl Dimension {
     pub fn new(width: u32, height: u32) -> Self {
         Dimension { width, height }
     }
+
+    /// Reads `WIDTHxHEIGHT`, where each side is in pixels or a physical unit
+    /// like `210mm` or `8.5in` at `dpi`.
+    pub fn parse(s: &str, dpi: f32) -> Result<Self> {
+        // The height is in pixels either way, and without its unit, the
+        // separator is the last `x` even when the width is in `px`.
+        let (width, height) = s
+            .strip_suffix("px")
+            .unwrap_or(s)
+            .rsplit_once('x')
+            .ok_or_else(|| PoetryWallError::DimensionReadError(String::from(s)))?;
+        let side = |part: &str| -> Result<u32> {
+            let length = part.parse::<Length>()?;
+            if length.unit == Unit::Percent {
+                return Err(PoetryWallError::DimensionReadError(String::from(s)));
+            }
+            let pixels = length.to_absolute_pixels(dpi, 0.0)?.round();
+            if pixels < 1.0 {
+                return Err(PoetryWallError::DimensionReadError(String::from(s)));
+            }
+            Ok(pixels as u32)
+        };
+        Ok(Dimension::new(side(width)?, side(height)?))
+    }
+
+    /// Reads a comma-separated list of dimensions and preset names, like
+    /// `phones,1920x1080`, leaving out any repeated sizes.
+    pub fn parse_list(s: &str, dpi: f32) -> Result<Vec<Self>> {
+        let mut dimensions = Vec::new();
+        for item in s.split(',').map(str::trim) {
+            let sizes = match PRESETS.iter().find(|(name, _)| *name == item) {
+                Some((_, sizes)) => sizes.iter().map(|&(width, height)| Dimension::new(width, height)).collect(),
+                None => vec![Dimension::parse(item, dpi)?],
+            };
+            for size in sizes {
+                if !dimensions.contains(&size) {
+                    dimensions.push(size);
+                }
+            }
+        }
+        Ok(dimensions)
+    }
 }
 
 impl FromStr for Dimension {
     type Err = PoetryWallError;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts = s.split('x').collect::<Vec<_>>();
-        if parts.len() == 2 {
-            Ok(Dimension::new(parts[0].parse()?, parts[1].parse()?))
-        } else {
-            Err(PoetryWallError::DimensionReadError(String::from(s)))
-        }
+    fn from_str(s: &str) -> Result<Self> {
+        Dimension::parse(s, DEFAULT_DPI)
     }
 }
 
@@ -38,7 +83,36 @@ mod tests {
     #[test]
     fn reads_from_string() {
         let dim = Dimension::from_str("256x1024");
-        assert_that(&dim).is_ok().is_equal_to(&Dimension::new(256, 1024));
+        assert_that(&dim).is_ok().is_equal_to(Dimension::new(256, 1024));
+    }
+
+    #[test]
+    fn reads_physical_sizes() {
+        let dim = Dimension::parse("8.5inx11in", 300.0);
+        assert_that(&dim).is_ok().is_equal_to(Dimension::new(2550, 3300));
+        assert_that(&Dimension::parse("210mmx50%", 300.0)).is_err();
+    }
+
+    #[test]
+    fn reads_pixel_units() {
+        for s in &["640pxx480px", "640pxx480", "640x480px"] {
+            assert_that(&Dimension::from_str(s)).is_ok().is_equal_to(Dimension::new(640, 480));
+        }
+        assert_that(&Dimension::parse("210mmx480px", 96.0)).is_ok().is_equal_to(Dimension::new(794, 480));
+        assert_that(&Dimension::from_str("640x480x320")).is_err();
+        assert_that(&Dimension::from_str("640pxx")).is_err();
+    }
+
+    #[test]
+    fn reads_lists_and_presets() {
+        let dims = Dimension::parse_list("1920x1080, phones", 96.0).unwrap();
+        assert_that(&dims.len()).is_equal_to(6);
+        assert_that(&dims[0]).is_equal_to(Dimension::new(1920, 1080));
+        assert_that(&dims[1]).is_equal_to(Dimension::new(1080, 2400));
+
+        let dims = Dimension::parse_list("desktops,1920x1080", 96.0).unwrap();
+        assert_that(&dims.len()).is_equal_to(4);
+        assert_that(&Dimension::parse_list("laptops", 96.0)).is_err();
     }
 
     #[test]
@@ -7,26 +7,65 @@ pub type Result<A> = result::Result<A, PoetryWallError>;
 pub enum PoetryWallError {
     InvalidMissingOption(String),
     IOError(io::Error),
-    FontReadError(rusttype::Error),
+    FontReadError(ab_glyph::InvalidFont),
     ColorError(Option<String>),
     DimensionReadError(String),
+    InvalidFont(String),
+    FeatureReadError(String),
+    VariationError(String),
+    SpacingReadError(String),
+    LanguageReadError(String),
+    FrontMatterError(String),
+    LayoutError(String),
+    LengthReadError(String),
+    BatchError(String),
+    ConfigError(String),
+    WatchError(String),
+    ApplyError(String),
+    HistoryError(String),
 }
 
 impl fmt::Display for PoetryWallError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             PoetryWallError::InvalidMissingOption(option) =>
-                write!(f, "Invalid/missing option: {}", &option),
+                write!(f, "Invalid/missing option: {}", option),
             PoetryWallError::IOError(err) =>
                 write!(f, "IO Error: {:?}", err),
             PoetryWallError::FontReadError(err) =>
                 write!(f, "Font reading error: {:?}", err),
             PoetryWallError::ColorError(Some(color)) =>
-                write!(f, "Invalid color name: {}", &color),
+                write!(f, "Invalid color name: {}", color),
             PoetryWallError::ColorError(None) =>
                 write!(f, "Missing color name"),
             PoetryWallError::DimensionReadError(value) =>
                 write!(f, "Invalid dimensions: {}", value),
+            PoetryWallError::InvalidFont(message) =>
+                write!(f, "Invalid font: {}", message),
+            PoetryWallError::FeatureReadError(value) =>
+                write!(f, "Invalid font feature: {}", value),
+            PoetryWallError::VariationError(message) =>
+                write!(f, "Invalid font variation: {}", message),
+            PoetryWallError::SpacingReadError(value) =>
+                write!(f, "Invalid spacing: {}", value),
+            PoetryWallError::LanguageReadError(value) =>
+                write!(f, "Invalid language: {}", value),
+            PoetryWallError::FrontMatterError(message) =>
+                write!(f, "Invalid front matter: {}", message),
+            PoetryWallError::LayoutError(message) =>
+                write!(f, "Invalid layout plan: {}", message),
+            PoetryWallError::LengthReadError(value) =>
+                write!(f, "Invalid length: {}", value),
+            PoetryWallError::BatchError(message) =>
+                write!(f, "Batch failed: {}", message),
+            PoetryWallError::ConfigError(message) =>
+                write!(f, "Invalid configuration: {}", message),
+            PoetryWallError::WatchError(message) =>
+                write!(f, "Unable to watch files: {}", message),
+            PoetryWallError::ApplyError(message) =>
+                write!(f, "Unable to set the wallpaper: {}", message),
+            PoetryWallError::HistoryError(message) =>
+                write!(f, "Unable to keep the rotation history: {}", message),
         }
     }
 }
@@ -36,10 +75,23 @@ impl Error for PoetryWallError {
         match self {
             PoetryWallError::InvalidMissingOption(_) =>
                 "invalid/missing option",
-            PoetryWallError::IOError(err) => err.description(),
-            PoetryWallError::FontReadError(err) => err.description(),
+            PoetryWallError::IOError(_) => "IO error",
+            PoetryWallError::FontReadError(_) => "font reading error",
             PoetryWallError::ColorError(_) => "invalid/missing color name",
             PoetryWallError::DimensionReadError(_) => "invalid dimension",
+            PoetryWallError::InvalidFont(_) => "invalid font",
+            PoetryWallError::FeatureReadError(_) => "invalid font feature",
+            PoetryWallError::VariationError(_) => "invalid font variation",
+            PoetryWallError::SpacingReadError(_) => "invalid spacing",
+            PoetryWallError::LanguageReadError(_) => "invalid language",
+            PoetryWallError::FrontMatterError(_) => "invalid front matter",
+            PoetryWallError::LayoutError(_) => "invalid layout plan",
+            PoetryWallError::LengthReadError(_) => "invalid length",
+            PoetryWallError::BatchError(_) => "batch failed",
+            PoetryWallError::ConfigError(_) => "invalid configuration",
+            PoetryWallError::WatchError(_) => "unable to watch files",
+            PoetryWallError::ApplyError(_) => "unable to set the wallpaper",
+            PoetryWallError::HistoryError(_) => "unable to keep the rotation history",
         }
     }
 }
@@ -50,14 +102,14 @@ impl From<io::Error> for PoetryWallError {
     }
 }
 
-impl From<rusttype::Error> for PoetryWallError {
-    fn from(err: rusttype::Error) -> Self {
+impl From<ab_glyph::InvalidFont> for PoetryWallError {
+    fn from(err: ab_glyph::InvalidFont) -> Self {
         PoetryWallError::FontReadError(err)
     }
 }
 
 impl From<num::ParseIntError> for PoetryWallError {
     fn from(error: num::ParseIntError) -> Self {
-        PoetryWallError::DimensionReadError(format!("Unable to parse number: {}", &error))
+        PoetryWallError::DimensionReadError(format!("Unable to parse number: {}", error))
     }
 }
\ No newline at end of file
@@ -1,15 +1,631 @@
-use rusttype::{PositionedGlyph, Font};
-use std::fs::File;
+use ab_glyph::{point, Font as _, FontVec, GlyphId, Outline, OutlineCurve, OutlinedGlyph, Point, PxScale,
+               Rect, ScaleFont, VariableFont};
+use rustybuzz::ttf_parser;
+use rustybuzz::Variation;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File};
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use crate::error::Result;
+use crate::bounding_box::BoundingBox;
+use crate::builtin::{builtin_face, builtin_name};
+use crate::error::{PoetryWallError, Result};
 
-pub type GlyphVec<'a> = Vec<PositionedGlyph<'a>>;
+pub type GlyphVec = Vec<PositionedGlyph>;
 
-pub fn load_font<'a, P: AsRef<Path>>(filename: &'a P) -> Result<Font<'a>> {
-    let mut font_file = File::open(filename)?;
-    let mut buffer = Vec::new();
-    font_file.read_to_end(&mut buffer)?;
-    Font::from_bytes(buffer).map_err(|e| e.into())
+/// The vertical metrics for a font at a given scale, in pixels. The descent
+/// is negative, since it's below the baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+/// A loaded font. This handles OpenType fonts with either TrueType (`glyf`)
+/// or PostScript (`CFF`/`CFF2`) outlines. The shaper reads the layout tables
+/// out of the same font data.
+#[derive(Debug)]
+pub struct Font {
+    font: FontVec,
+    variations: Vec<Variation>,
+}
+
+impl Font {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let font = FontVec::try_from_vec(data)?;
+        if rustybuzz::Face::from_slice(font.as_slice(), 0).is_none() {
+            return Err(PoetryWallError::InvalidFont(String::from(
+                "unable to read the OpenType tables",
+            )));
+        }
+        Ok(Font {
+            font,
+            variations: Vec::new(),
+        })
+    }
+
+    /// The shaper's view of the font, with the current variations applied.
+    pub fn face(&self) -> rustybuzz::Face<'_> {
+        let mut face = rustybuzz::Face::from_slice(self.font.as_slice(), 0)
+            .expect("font data validated on load");
+        face.set_variations(&self.variations);
+        face
+    }
+
+    /// Picks an instance of a variable font. It's an error to set an axis
+    /// that the font doesn't have.
+    pub fn set_variations(&mut self, variations: &FontVariations) -> Result<()> {
+        for variation in variations.variations() {
+            if !self.set_variation(&variation.tag.to_bytes(), variation.value) {
+                return Err(PoetryWallError::VariationError(format!(
+                    "the font doesn't have a '{}' axis",
+                    variation.tag
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets one design axis, returning `false` if the font doesn't have it.
+    pub fn set_variation(&mut self, tag: &[u8; 4], value: f32) -> bool {
+        if !self.has_axis(tag) || !self.font.set_variation(tag, value) {
+            return false;
+        }
+        let tag = rustybuzz::ttf_parser::Tag::from_bytes(tag);
+        self.variations.retain(|variation| variation.tag != tag);
+        self.variations.push(Variation { tag, value });
+        true
+    }
+
+    pub fn has_axis(&self, tag: &[u8; 4]) -> bool {
+        self.font.variations().iter().any(|axis| &axis.tag == tag)
+    }
+
+    /// Sets the `opsz` axis to match the size the text is set at. Like CSS,
+    /// this treats a pixel as a point.
+    pub fn set_optical_size(&mut self, scale: PxScale) -> bool {
+        let em_size = self.em_size(scale);
+        self.set_variation(b"opsz", em_size)
+    }
+
+    pub fn v_metrics(&self, scale: PxScale) -> VMetrics {
+        let scaled = self.font.as_scaled(scale);
+        VMetrics {
+            ascent: scaled.ascent(),
+            descent: scaled.descent(),
+            line_gap: scaled.line_gap(),
+        }
+    }
+
+    /// The height of the capital letters in pixels. Fonts that don't say
+    /// get an estimate from the ascent.
+    pub fn cap_height(&self, scale: PxScale) -> f32 {
+        match self.face().capital_height() {
+            Some(height) if height > 0 => height as f32 * self.pixels_per_unit(scale),
+            _ => 0.7 * self.v_metrics(scale).ascent,
+        }
+    }
+
+    /// The size of an em in pixels at this scale.
+    pub fn em_size(&self, scale: PxScale) -> f32 {
+        self.font.units_per_em().unwrap_or(1000.0) * self.pixels_per_unit(scale)
+    }
+
+    /// The number of pixels per font unit at this scale. The scale is the
+    /// height from the descender to the ascender.
+    pub fn pixels_per_unit(&self, scale: PxScale) -> f32 {
+        self.font.as_scaled(scale).h_scale_factor()
+    }
+
+    /// Places a glyph with its origin on the baseline at `position`, and
+    /// outlines it for rendering.
+    pub fn glyph(&self, glyph_id: u32, scale: PxScale, position: Point, synthesis: Synthesis) -> PositionedGlyph {
+        let glyph = GlyphId(glyph_id as u16).with_scale_and_position(scale, position);
+        let scale_factor = self.font.as_scaled(scale).scale_factor();
+        let outline = self.font.outline(glyph.id).map(|outline| {
+            let outline = if synthesis.oblique { slant(outline) } else { outline };
+            OutlinedGlyph::new(glyph, outline, scale_factor)
+        });
+        PositionedGlyph {
+            outline,
+            embolden: synthesis.embolden_width(scale),
+        }
+    }
+
+    /// The family name from the `name` table, preferring the typographic
+    /// family over the legacy one.
+    pub fn family_name(&self) -> Option<String> {
+        let face = ttf_parser::Face::parse(self.font.as_slice(), 0).ok()?;
+        family_name(&face)
+    }
+}
+
+fn family_name(face: &ttf_parser::Face) -> Option<String> {
+    let names = face.names();
+    [ttf_parser::name_id::TYPOGRAPHIC_FAMILY, ttf_parser::name_id::FAMILY]
+        .iter()
+        .filter_map(|&id| {
+            names
+                .into_iter()
+                .filter(|name| name.name_id == id && name.is_unicode())
+                .find_map(|name| name.to_string())
+        })
+        .next()
+}
+
+/// How far faux oblique slants the glyphs, as the tangent of the angle. This
+/// is about 11°.
+const OBLIQUE_SLANT: f32 = 0.2;
+
+/// Shears an outline to the right. This works in font units, where the y-axis
+/// points up, so the baseline stays put.
+fn slant(outline: Outline) -> Outline {
+    let shear = |p: Point| point(p.x + p.y * OBLIQUE_SLANT, p.y);
+    // ab_glyph keeps the top of the glyph in `min.y`, so don't rely on which
+    // corner holds the lower y.
+    let Rect { min, max } = outline.bounds;
+    let (bottom, top) = (min.y.min(max.y), min.y.max(max.y));
+    Outline {
+        bounds: Rect {
+            min: point(min.x + bottom * OBLIQUE_SLANT, min.y),
+            max: point(max.x + top * OBLIQUE_SLANT, max.y),
+        },
+        curves: outline
+            .curves
+            .into_iter()
+            .map(|curve| match curve {
+                OutlineCurve::Line(p0, p1) => OutlineCurve::Line(shear(p0), shear(p1)),
+                OutlineCurve::Quad(p0, p1, p2) => OutlineCurve::Quad(shear(p0), shear(p1), shear(p2)),
+                OutlineCurve::Cubic(p0, p1, p2, p3) => {
+                    OutlineCurve::Cubic(shear(p0), shear(p1), shear(p2), shear(p3))
+                }
+            })
+            .collect(),
+    }
+}
+
+/// The four faces of a family that Markdown emphasis can ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl FontStyle {
+    pub fn new(bold: bool, italic: bool) -> Self {
+        match (bold, italic) {
+            (false, false) => FontStyle::Regular,
+            (true, false) => FontStyle::Bold,
+            (false, true) => FontStyle::Italic,
+            (true, true) => FontStyle::BoldItalic,
+        }
+    }
+
+    pub fn is_bold(self) -> bool {
+        self == FontStyle::Bold || self == FontStyle::BoldItalic
+    }
+
+    pub fn is_italic(self) -> bool {
+        self == FontStyle::Italic || self == FontStyle::BoldItalic
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// What to fake when a family doesn't have the face a style asks for.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Synthesis {
+    pub embolden: bool,
+    pub oblique: bool,
+}
+
+impl Synthesis {
+    /// How many pixels faux bold smears each glyph to the right.
+    pub fn embolden_width(&self, scale: PxScale) -> u32 {
+        if self.embolden {
+            (scale.x * 0.03).round().max(1.0) as u32
+        } else {
+            0
+        }
+    }
+}
+
+/// A regular font and the bold, italic and bold italic faces that go with it.
+/// Faces that can't be found are synthesized from the closest one that can.
+#[derive(Debug)]
+pub struct FontFamily {
+    fonts: Vec<Font>,
+    faces: [(usize, Synthesis); 4],
+}
+
+impl FontFamily {
+    /// A family where every style is synthesized from the regular font.
+    pub fn new(regular: Font) -> Self {
+        FontFamily::from_fonts(regular, [None, None, None])
+    }
+
+    /// Loads the regular font and looks for the other faces in the same
+    /// directory. A file whose name is the regular font's name plus `Bold`,
+    /// `Italic`, `Oblique`, `BoldItalic` or `BoldOblique` (ignoring case,
+    /// hyphens and underscores) is used first. After that, any font with the
+    /// same family name in its `name` table is classified by its style flags.
+    /// The built-in fonts come with all four faces.
+    pub fn discover<P: AsRef<Path>>(regular_file: P) -> Result<Self> {
+        let regular_file = regular_file.as_ref();
+        let regular = load_font(&regular_file)?;
+        if builtin_name(regular_file).is_some() {
+            let load = |style| load_face(regular_file, style).map(Some);
+            return Ok(FontFamily::from_fonts(
+                regular,
+                [load(FontStyle::Bold)?, load(FontStyle::Italic)?, load(FontStyle::BoldItalic)?],
+            ));
+        }
+        let siblings = sibling_fonts(regular_file);
+
+        let mut found: [Option<PathBuf>; 3] = [None, None, None];
+        let base = style_base_name(regular_file);
+        for sibling in &siblings {
+            if let Some(style) = style_from_file_name(&base, sibling) {
+                found[style.index() - 1].get_or_insert_with(|| sibling.clone());
+            }
+        }
+
+        if found.iter().any(Option::is_none) {
+            if let Some(family) = regular.family_name() {
+                for sibling in &siblings {
+                    if let Some(style) = style_from_name_table(&family, sibling) {
+                        found[style.index() - 1].get_or_insert_with(|| sibling.clone());
+                    }
+                }
+            }
+        }
+
+        let [bold, italic, bold_italic] = found;
+        let load = |file: Option<PathBuf>| file.map(|file| load_font(&file)).transpose();
+        Ok(FontFamily::from_fonts(regular, [load(bold)?, load(italic)?, load(bold_italic)?]))
+    }
+
+    fn from_fonts(regular: Font, others: [Option<Font>; 3]) -> Self {
+        let mut fonts = vec![regular];
+        let mut index = [None; 4];
+        index[0] = Some(0);
+        for (i, font) in IntoIterator::into_iter(others).enumerate() {
+            if let Some(font) = font {
+                fonts.push(font);
+                index[i + 1] = Some(fonts.len() - 1);
+            }
+        }
+
+        let synthesized = |font: usize, embolden: bool, oblique: bool| (font, Synthesis { embolden, oblique });
+        let regular = (0, Synthesis::default());
+        let bold = index[1].map_or(synthesized(0, true, false), |i| (i, Synthesis::default()));
+        let italic = index[2].map_or(synthesized(0, false, true), |i| (i, Synthesis::default()));
+        let bold_italic = match (index[3], index[2], index[1]) {
+            (Some(i), _, _) => (i, Synthesis::default()),
+            (None, Some(i), _) => synthesized(i, true, false),
+            (None, None, Some(i)) => synthesized(i, false, true),
+            (None, None, None) => synthesized(0, true, true),
+        };
+
+        FontFamily {
+            fonts,
+            faces: [regular, bold, italic, bold_italic],
+        }
+    }
+
+    pub fn regular(&self) -> &Font {
+        &self.fonts[0]
+    }
+
+    /// The font to set a style in, and what needs to be faked.
+    pub fn face(&self, style: FontStyle) -> (&Font, Synthesis) {
+        let (font, synthesis) = self.faces[style.index()];
+        (&self.fonts[font], synthesis)
+    }
+
+    /// Applies the variations to every face. It's only an error if the
+    /// regular font is missing an axis, since the other faces are often
+    /// static fonts.
+    pub fn set_variations(&mut self, variations: &FontVariations) -> Result<()> {
+        self.fonts[0].set_variations(variations)?;
+        for font in self.fonts.iter_mut().skip(1) {
+            for variation in variations.variations() {
+                font.set_variation(&variation.tag.to_bytes(), variation.value);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_axis(&self, tag: &[u8; 4]) -> bool {
+        self.fonts.iter().any(|font| font.has_axis(tag))
+    }
+
+    pub fn set_optical_size(&mut self, scale: PxScale) {
+        for font in self.fonts.iter_mut() {
+            font.set_optical_size(scale);
+        }
+    }
+}
+
+pub(crate) fn sibling_fonts(regular_file: &Path) -> Vec<PathBuf> {
+    let directory = match regular_file.parent() {
+        Some(directory) if directory.as_os_str().is_empty() => Path::new("."),
+        Some(directory) => directory,
+        None => Path::new("."),
+    };
+    let mut siblings = fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.file_name() != regular_file.file_name())
+                .filter(|path| {
+                    path.extension()
+                        .and_then(|extension| extension.to_str())
+                        .is_some_and(|extension| {
+                            extension.eq_ignore_ascii_case("ttf") || extension.eq_ignore_ascii_case("otf")
+                        })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    siblings.sort();
+    siblings
+}
+
+fn normalized_stem(file: &Path) -> String {
+    file.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("")
+        .chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn style_base_name(regular_file: &Path) -> String {
+    let stem = normalized_stem(regular_file);
+    ["regular", "book", "roman"]
+        .iter()
+        .find_map(|suffix| stem.strip_suffix(suffix))
+        .unwrap_or(&stem)
+        .to_string()
+}
+
+fn style_from_file_name(base: &str, file: &Path) -> Option<FontStyle> {
+    match normalized_stem(file).strip_prefix(base)? {
+        "bold" => Some(FontStyle::Bold),
+        "italic" | "oblique" => Some(FontStyle::Italic),
+        "bolditalic" | "boldoblique" => Some(FontStyle::BoldItalic),
+        _ => None,
+    }
+}
+
+fn style_from_name_table(family: &str, file: &Path) -> Option<FontStyle> {
+    let data = fs::read(file).ok()?;
+    let face = ttf_parser::Face::parse(&data, 0).ok()?;
+    if family_name(&face)?.as_str() != family {
+        return None;
+    }
+    match FontStyle::new(face.is_bold(), face.is_italic() || face.is_oblique()) {
+        FontStyle::Regular => None,
+        style => Some(style),
+    }
+}
+
+/// Settings for a variable font's design axes, such as weight or optical
+/// size.
+#[derive(Debug, Clone, Default)]
+pub struct FontVariations(Vec<Variation>);
+
+impl FontVariations {
+    pub fn new(variations: Vec<Variation>) -> Self {
+        FontVariations(variations)
+    }
+
+    pub fn variations(&self) -> &[Variation] {
+        &self.0
+    }
+
+    pub fn contains(&self, tag: &[u8; 4]) -> bool {
+        self.0.iter().any(|variation| &variation.tag.to_bytes() == tag)
+    }
+}
+
+impl fmt::Display for FontVariations {
+    /// Writes the settings the way `from_str` reads them.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let settings = self
+            .0
+            .iter()
+            .map(|variation| format!("{}={}", variation.tag, variation.value))
+            .collect::<Vec<_>>();
+        write!(f, "{}", settings.join(","))
+    }
+}
+
+impl FromStr for FontVariations {
+    type Err = PoetryWallError;
+
+    /// Reads a comma-separated list of axis settings, such as
+    /// `wght=350,opsz=24`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|variation| !variation.is_empty())
+            .map(|variation| {
+                Variation::from_str(variation)
+                    .map_err(|_| PoetryWallError::VariationError(format!("unable to read '{}'", variation)))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(FontVariations)
+    }
+}
+
+/// A glyph that's been scaled and placed on the canvas. Glyphs without any
+/// ink, like spaces, don't have an outline.
+#[derive(Debug, Clone)]
+pub struct PositionedGlyph {
+    outline: Option<OutlinedGlyph>,
+    embolden: u32,
+}
+
+impl PositionedGlyph {
+    /// The whole-pixel box that `draw` will cover.
+    pub fn pixel_bounding_box(&self) -> Option<BoundingBox> {
+        self.outline.as_ref().map(|outline| {
+            let bounds = outline.px_bounds();
+            BoundingBox {
+                top: bounds.min.y as i32,
+                left: bounds.min.x as i32,
+                bottom: bounds.max.y as i32,
+                right: bounds.max.x as i32 + self.embolden as i32,
+            }
+        })
+    }
+
+    /// Calls `o` with the coverage for each pixel, relative to the top-left of
+    /// the pixel bounding box. Faux bold draws the outline several times,
+    /// shifted a pixel to the right each time.
+    pub fn draw<O: FnMut(u32, u32, f32)>(&self, mut o: O) {
+        let outline = match &self.outline {
+            Some(outline) => outline,
+            None => return,
+        };
+        if self.embolden == 0 {
+            outline.draw(o);
+            return;
+        }
+
+        let bounds = outline.px_bounds();
+        let width = bounds.width() as u32 + self.embolden;
+        let mut coverage = vec![0.0f32; (width * bounds.height() as u32) as usize];
+        outline.draw(|x, y, v| {
+            for dx in 0..=self.embolden {
+                let i = (y * width + x + dx) as usize;
+                coverage[i] = coverage[i].max(v);
+            }
+        });
+        for (i, v) in coverage.into_iter().enumerate() {
+            o(i as u32 % width, i as u32 / width, v);
+        }
+    }
+}
+
+/// Loads a font file, or one of the built-in fonts if the name starts with
+/// `builtin:`.
+pub fn load_font<P: AsRef<Path>>(filename: &P) -> Result<Font> {
+    load_face(filename.as_ref(), FontStyle::Regular)
+}
+
+fn load_face(filename: &Path, style: FontStyle) -> Result<Font> {
+    let buffer = match builtin_name(filename) {
+        Some(name) => builtin_face(name, style)?.to_vec(),
+        None => {
+            let mut font_file = File::open(filename)?;
+            let mut buffer = Vec::new();
+            font_file.read_to_end(&mut buffer)?;
+            buffer
+        }
+    };
+    Font::from_bytes(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ab_glyph::{point, PxScale};
+    use spectral::prelude::*;
+
+    use crate::font::{load_font, Font, FontFamily, FontStyle, FontVariations, Synthesis};
+    use crate::shaping::{shape_line, FontFeatures};
+
+    fn glyph_width(font: &Font, text: &str) -> i32 {
+        let shaped = shape_line(font, text, &FontFeatures::default());
+        let glyph = font.glyph(shaped[0].glyph_id, PxScale::from(48.0), point(0.0, 48.0), Synthesis::default());
+        let bb = glyph.pixel_bounding_box().unwrap();
+        bb.right - bb.left
+    }
+
+    #[test]
+    fn reads_cff_outlines() {
+        let font = load_font(&"./tests/fixtures/Cantarell-VF.otf").unwrap();
+        let shaped = shape_line(&font, "A", &FontFeatures::default());
+        let glyph = font.glyph(shaped[0].glyph_id, PxScale::from(48.0), point(0.0, 48.0), Synthesis::default());
+        assert_that(&glyph.pixel_bounding_box()).is_some();
+    }
+
+    #[test]
+    fn returns_error_invalid_font() {
+        let font = load_font(&"./tests/fixtures/fly-buzz.md");
+        assert_that(&font).is_err();
+    }
+
+    #[test]
+    fn reads_variations_from_string() {
+        let variations = FontVariations::from_str("wght=350, opsz=24");
+        assert_that(&variations)
+            .is_ok()
+            .map(|v| &v.0)
+            .has_length(2);
+    }
+
+    #[test]
+    fn writes_variations_as_read() {
+        let variations = FontVariations::from_str("wght=350, opsz=24").unwrap();
+        assert_that(&variations.to_string()).is_equal_to(String::from("wght=350,opsz=24"));
+    }
+
+    #[test]
+    fn fails_on_invalid_variation() {
+        let variations = FontVariations::from_str("wght");
+        assert_that(&variations).is_err();
+    }
+
+    #[test]
+    fn sets_variable_font_instance() {
+        let mut font = load_font(&"./tests/fixtures/Cantarell-VF.otf").unwrap();
+        font.set_variations(&FontVariations::from_str("wght=100").unwrap()).unwrap();
+        let thin = glyph_width(&font, "o");
+        font.set_variations(&FontVariations::from_str("wght=800").unwrap()).unwrap();
+        let bold = glyph_width(&font, "o");
+        assert_that(&bold).is_greater_than(thin);
+    }
+
+    #[test]
+    fn returns_error_missing_axis() {
+        let mut font = load_font(&"./tests/fixtures/Cantarell-VF.otf").unwrap();
+        let result = font.set_variations(&FontVariations::from_str("wdth=75").unwrap());
+        assert_that(&result).is_err();
+    }
+
+    #[test]
+    fn discovers_sibling_faces() {
+        let family = FontFamily::discover("./tests/fixtures/DejaVuSansMono.ttf").unwrap();
+        for style in &[FontStyle::Bold, FontStyle::Italic, FontStyle::BoldItalic] {
+            let (font, synthesis) = family.face(*style);
+            assert_that(&synthesis).is_equal_to(Synthesis::default());
+            assert_that(&font.family_name()).is_equal_to(family.regular().family_name());
+        }
+    }
+
+    #[test]
+    fn synthesizes_missing_faces() {
+        let font = load_font(&"./tests/fixtures/Cantarell-VF.otf").unwrap();
+        let family = FontFamily::discover("./tests/fixtures/Cantarell-VF.otf").unwrap();
+        let (_, synthesis) = family.face(FontStyle::BoldItalic);
+        assert_that(&synthesis).is_equal_to(Synthesis {
+            embolden: true,
+            oblique: true,
+        });
+        let (_, synthesis) = FontFamily::new(font).face(FontStyle::Regular);
+        assert_that(&synthesis).is_equal_to(Synthesis::default());
+    }
 }
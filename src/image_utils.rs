@@ -1,6 +1,17 @@
-use image::{ImageBuffer, Rgba};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use image::bmp::BMPEncoder;
+use image::jpeg::JPEGEncoder;
+use image::png::PNGEncoder;
+use image::{ColorType, DynamicImage, ImageBuffer, Rgba};
+use palette::rgb::Rgb;
+use crate::bounding_box::BoundingBox;
 use crate::font::GlyphVec;
 use crate::color::Color;
+use crate::error::{PoetryWallError, Result};
 
 pub type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
 
@@ -9,14 +20,138 @@ pub fn create_image<U8: Into<u8>>(width: u32, height: u32, red: U8, green: U8, b
     ImageBuffer::from_pixel(width, height, Rgba(background))
 }
 
-pub fn render_glyphs(image: &mut Image, glyphs: &GlyphVec, color: &Color, background: &Color) {
+const METERS_PER_INCH: f32 = 0.0254;
+
+/// The file formats images can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Bmp,
+}
+
+impl ImageFormat {
+    /// The format from a file's extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    /// The usual extension for files in the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Bmp => "bmp",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "jpeg" | "jpg" => Ok(ImageFormat::Jpeg),
+            "bmp" => Ok(ImageFormat::Bmp),
+            _ => Err(PoetryWallError::InvalidMissingOption(format!("format: {}", s))),
+        }
+    }
+}
+
+/// Saves the image in the format given, or else the one from the file's
+/// extension. The image is encoded before anything is written, so an error
+/// doesn't leave part of it behind.
+pub fn save_image<P: AsRef<Path>>(image: &Image, path: P, format: Option<ImageFormat>, dpi: Option<f32>) -> Result<()> {
+    let path = path.as_ref();
+    let format = format
+        .or_else(|| ImageFormat::from_path(path))
+        .ok_or_else(|| PoetryWallError::InvalidMissingOption(format!("format: can't tell from {}", path.display())))?;
+    fs::write(path, encode_image(image, format, dpi)?)?;
+    Ok(())
+}
+
+/// Writes the image to standard output, which needs the format given.
+pub fn print_image(image: &Image, format: Option<ImageFormat>, dpi: Option<f32>) -> Result<()> {
+    let format = format.ok_or_else(|| {
+        PoetryWallError::InvalidMissingOption(String::from("format: it's needed to write to stdout"))
+    })?;
+    let bytes = encode_image(image, format, dpi)?;
+    let mut stdout = io::stdout();
+    stdout.write_all(&bytes)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Encodes the image. With a resolution, a PNG records it in its pHYs chunk,
+/// so it prints at the right size.
+pub fn encode_image(image: &Image, format: ImageFormat, dpi: Option<f32>) -> Result<Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let mut bytes = Vec::new();
+    match format {
+        ImageFormat::Png => {
+            PNGEncoder::new(&mut bytes).encode(image, width, height, ColorType::RGBA(8))?;
+            if let Some(dpi) = dpi {
+                bytes = with_resolution(&bytes, dpi);
+            }
+        }
+        ImageFormat::Jpeg => {
+            let rgb = DynamicImage::ImageRgba8(image.clone()).to_rgb();
+            JPEGEncoder::new_with_quality(&mut bytes, 90).encode(&rgb, width, height, ColorType::RGB(8))?;
+        }
+        ImageFormat::Bmp => BMPEncoder::new(&mut bytes).encode(image, width, height, ColorType::RGBA(8))?,
+    }
+    Ok(bytes)
+}
+
+/// Adds a pHYs chunk giving the resolution to a PNG, right after its header.
+fn with_resolution(png: &[u8], dpi: f32) -> Vec<u8> {
+    // The signature and the IHDR chunk always come first.
+    let header_end = 8 + 4 + 4 + 13 + 4;
+    let pixels_per_meter = (dpi / METERS_PER_INCH).round() as u32;
+    let mut chunk = b"pHYs".to_vec();
+    chunk.extend_from_slice(&pixels_per_meter.to_be_bytes());
+    chunk.extend_from_slice(&pixels_per_meter.to_be_bytes());
+    chunk.push(1);
+
+    let mut output = png[..header_end].to_vec();
+    output.extend_from_slice(&(chunk.len() as u32 - 4).to_be_bytes());
+    output.extend_from_slice(&chunk);
+    output.extend_from_slice(&crc32(&chunk).to_be_bytes());
+    output.extend_from_slice(&png[header_end..]);
+    output
+}
+
+/// The CRC that PNG chunks end with.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Draws the glyphs over whatever's already in the image, so glyphs whose
+/// boxes overlap, such as italics, don't clip each other.
+pub fn render_glyphs(image: &mut Image, glyphs: &GlyphVec, color: &Color) {
     for glyph in glyphs {
         if let Some(bounding_box) = glyph.pixel_bounding_box() {
             glyph.draw(|x, y, v| {
-                let pixel_color = color.alpha_composite(background, v);
+                let x = x as i32 + bounding_box.left;
+                let y = y as i32 + bounding_box.top;
+                if v <= 0.0 || x < 0 || y < 0 || x as u32 >= image.width() || y as u32 >= image.height() {
+                    return;
+                }
+                let (x, y) = (x as u32, y as u32);
+                let Rgba { data: [red, green, blue, _] } = *image.get_pixel(x, y);
+                let background = Color::new(Rgb::new(red, green, blue));
+                let pixel_color = color.alpha_composite(&background, v.min(1.0));
                 image.put_pixel(
-                    x + bounding_box.min.x as u32,
-                    y + bounding_box.min.y as u32,
+                    x,
+                    y,
                     Rgba {
                         data: pixel_color.as_array(),
                     },
@@ -26,15 +161,109 @@ pub fn render_glyphs(image: &mut Image, glyphs: &GlyphVec, color: &Color, backgr
     }
 }
 
+/// Draws vertical rules `width` pixels wide centered on each of the `xs`,
+/// running from the top of the box to the bottom.
+pub fn draw_rules(image: &mut Image, xs: &[f32], extent: &BoundingBox, width: f32, color: &Color) {
+    if extent.is_empty() {
+        return;
+    }
+    let top = extent.top.max(0) as u32;
+    let bottom = (extent.bottom.max(0) as u32).min(image.height());
+    let pixel = Rgba { data: color.as_array() };
+    for x in xs {
+        let left = (x - width / 2.0).round().max(0.0) as u32;
+        let right = ((x + width / 2.0).round().max(0.0) as u32).min(image.width()).max(left + 1);
+        for x in left..right.min(image.width()) {
+            for y in top..bottom {
+                image.put_pixel(x, y, pixel);
+            }
+        }
+    }
+}
+
+/// Draws a one-pixel line across at `y` from `left` to `right`, clipped to the
+/// image.
+pub fn draw_hline(image: &mut Image, y: f32, left: f32, right: f32, color: Rgba<u8>) {
+    let y = y.round();
+    if y < 0.0 || y >= image.height() as f32 {
+        return;
+    }
+    let left = left.round().max(0.0) as u32;
+    let right = (right.round().max(0.0) as u32).min(image.width());
+    for x in left..right {
+        image.put_pixel(x, y as u32, color);
+    }
+}
+
+/// Draws a one-pixel line down at `x` from `top` to `bottom`, clipped to the
+/// image.
+pub fn draw_vline(image: &mut Image, x: f32, top: f32, bottom: f32, color: Rgba<u8>) {
+    let x = x.round();
+    if x < 0.0 || x >= image.width() as f32 {
+        return;
+    }
+    let top = top.round().max(0.0) as u32;
+    let bottom = (bottom.round().max(0.0) as u32).min(image.height());
+    for y in top..bottom {
+        image.put_pixel(x as u32, y, color);
+    }
+}
+
+/// Draws the outline of a box. Empty boxes aren't drawn.
+pub fn draw_box(image: &mut Image, bounding_box: &BoundingBox, color: Rgba<u8>) {
+    if bounding_box.is_empty() {
+        return;
+    }
+    let (top, left) = (bounding_box.top as f32, bounding_box.left as f32);
+    let (bottom, right) = (bounding_box.bottom as f32, bounding_box.right as f32);
+    draw_hline(image, top, left, right + 1.0, color);
+    draw_hline(image, bottom, left, right + 1.0, color);
+    draw_vline(image, left, top, bottom + 1.0, color);
+    draw_vline(image, right, top, bottom + 1.0, color);
+}
+
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
     use test::Bencher;
 
     use image::{DynamicImage, ImageBuffer, Rgba};
+    use spectral::prelude::*;
+
+    use crate::image_utils::{crc32, create_image, encode_image, with_resolution, ImageFormat};
 
     static WIDTH: u32 = 2880;
     static HEIGHT: u32 = 2560;
 
+    #[test]
+    fn computes_png_crcs() {
+        assert_that(&crc32(b"IEND")).is_equal_to(0xAE42_6082);
+    }
+
+    #[test]
+    fn records_resolution() {
+        let png = vec![0u8; 33];
+        let output = with_resolution(&png, 300.0);
+        assert_that(&output.len()).is_equal_to(33 + 21);
+        assert_that(&&output[37..41]).is_equal_to(&b"pHYs"[..]);
+        // 300 dpi is 11811 pixels per meter.
+        assert_that(&&output[41..45]).is_equal_to(&11811u32.to_be_bytes()[..]);
+        assert_that(&output[49]).is_equal_to(1);
+    }
+
+    #[test]
+    fn encodes_each_format() {
+        assert_that(&ImageFormat::from_path(Path::new("wall.JPG"))).is_equal_to(Some(ImageFormat::Jpeg));
+        assert_that(&ImageFormat::from_path(Path::new("-"))).is_none();
+        let image = create_image(4, 4, 10, 20, 30);
+        let png = encode_image(&image, ImageFormat::Png, None).unwrap();
+        assert_that(&&png[1..4]).is_equal_to(&b"PNG"[..]);
+        let jpeg = encode_image(&image, ImageFormat::Jpeg, None).unwrap();
+        assert_that(&&jpeg[..2]).is_equal_to(&[0xFF, 0xD8][..]);
+        let bmp = encode_image(&image, ImageFormat::Bmp, None).unwrap();
+        assert_that(&&bmp[..2]).is_equal_to(&b"BM"[..]);
+    }
+
     // bench:  31,124,014 ns/iter (+/- 20,982,095)
     // Using this one because the others really only work with a white/gray/black
     // background color. One that fills in all places in the vector with the same
@@ -53,7 +282,7 @@ mod tests {
     // bench:  15,440,571 ns/iter (+/- 2,403,413)
     #[bench]
     fn set_background_from_pixel(b: &mut Bencher) {
-        let background = [255 as u8, 255, 255, 255];
+        let background = [255_u8, 255, 255, 255];
         b.iter(|| {
             ImageBuffer::from_pixel(WIDTH, HEIGHT, Rgba(background));
         });
@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ab_glyph::{point, PxScale};
+use serde::{Deserialize, Serialize};
+
+use crate::bounding_box::BoundingBox;
+use crate::builtin::builtin_name;
+use crate::error::{PoetryWallError, Result};
+use crate::font::{FontFamily, FontStyle, GlyphVec};
+
+/// Everything needed to draw a poem, with the layout worked out but nothing
+/// rendered yet. It can be written out as JSON, adjusted or generated by
+/// other tools, and read back in to render.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutPlan {
+    pub width: u32,
+    pub height: u32,
+    /// The font file, or a built-in font name.
+    pub font: PathBuf,
+    /// The variable font axis settings, like `wght=350,opsz=24`.
+    pub variations: String,
+    /// Whether to set the optical size axis from each line's size.
+    pub auto_optical_size: bool,
+    pub font_size: f32,
+    pub top_offset: f32,
+    pub left_offset: f32,
+    /// The box covering the ink on all of the pages.
+    pub ink_box: BoundingBox,
+    /// The box covering the lines on all of the pages.
+    pub line_box: BoundingBox,
+    pub pages: Vec<PagePlan>,
+}
+
+/// One image's worth of lines.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PagePlan {
+    pub lines: Vec<LinePlan>,
+    pub ink_box: BoundingBox,
+    pub line_box: BoundingBox,
+    /// Where the rules between columns go, halfway across each gap.
+    pub column_rules: Vec<f32>,
+}
+
+/// A line of text and where its glyphs go.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinePlan {
+    pub text: String,
+    /// The size of the type, in pixels.
+    pub size: f32,
+    /// Where the line starts and how wide it is, leaving out any hanging
+    /// punctuation.
+    pub left: f32,
+    pub width: f32,
+    pub baseline: f32,
+    pub glyphs: Vec<GlyphPlan>,
+}
+
+/// A glyph in one of the family's faces, with its origin on the baseline in
+/// image pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GlyphPlan {
+    pub id: u32,
+    pub style: FontStyle,
+    pub x: f32,
+    pub y: f32,
+}
+
+impl LayoutPlan {
+    /// Reads a plan from a file. A relative font path is taken to be from
+    /// the plan's directory, since the plan could be used from anywhere.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut plan = LayoutPlan::from_json(&fs::read_to_string(path)?)?;
+        if plan.font.is_relative() && builtin_name(&plan.font).is_none() {
+            plan.font = path.parent().unwrap_or(Path::new("")).join(&plan.font);
+        }
+        Ok(plan)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|err| PoetryWallError::LayoutError(err.to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|err| PoetryWallError::LayoutError(err.to_string()))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// Whether any of the glyphs are in a face other than the regular one.
+    pub fn has_emphasis(&self) -> bool {
+        self.pages
+            .iter()
+            .flat_map(|page| &page.lines)
+            .flat_map(|line| &line.glyphs)
+            .any(|glyph| glyph.style != FontStyle::Regular)
+    }
+}
+
+impl LinePlan {
+    /// Scales and places the line's glyphs in the family's faces.
+    pub fn positioned_glyphs(&self, family: &FontFamily) -> GlyphVec {
+        let scale = PxScale::from(self.size);
+        self.glyphs
+            .iter()
+            .map(|glyph| {
+                let (font, synthesis) = family.face(glyph.style);
+                font.glyph(glyph.id, scale, point(glyph.x, glyph.y), synthesis)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use spectral::prelude::*;
+
+    use crate::bounding_box::BoundingBox;
+    use crate::font::FontStyle;
+    use crate::layout::{GlyphPlan, LayoutPlan, LinePlan, PagePlan};
+
+    #[test]
+    fn finds_fonts_next_to_the_plan() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let plan_file = dir.path().join("plan.json");
+        let mut plan = LayoutPlan {
+            width: 640,
+            height: 480,
+            font: PathBuf::from("fonts/Gentium.ttf"),
+            variations: String::new(),
+            auto_optical_size: false,
+            font_size: 24.0,
+            top_offset: 0.0,
+            left_offset: 0.0,
+            ink_box: BoundingBox::empty(),
+            line_box: BoundingBox::empty(),
+            pages: Vec::new(),
+        };
+        plan.write(&plan_file).unwrap();
+        assert_that(&LayoutPlan::from_file(&plan_file).unwrap().font).is_equal_to(dir.path().join("fonts/Gentium.ttf"));
+
+        plan.font = PathBuf::from("builtin:serif");
+        plan.write(&plan_file).unwrap();
+        assert_that(&LayoutPlan::from_file(&plan_file).unwrap().font).is_equal_to(PathBuf::from("builtin:serif"));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let plan = LayoutPlan {
+            width: 1920,
+            height: 1080,
+            font: PathBuf::from("builtin:serif"),
+            variations: String::new(),
+            auto_optical_size: false,
+            font_size: 72.0,
+            top_offset: 100.0,
+            left_offset: 200.0,
+            ink_box: BoundingBox::empty(),
+            line_box: BoundingBox::empty(),
+            pages: vec![PagePlan {
+                lines: vec![LinePlan {
+                    text: String::from("I"),
+                    size: 72.0,
+                    left: 200.0,
+                    width: 20.0,
+                    baseline: 167.0,
+                    glyphs: vec![GlyphPlan {
+                        id: 44,
+                        style: FontStyle::Italic,
+                        x: 200.0,
+                        y: 167.0,
+                    }],
+                }],
+                ink_box: BoundingBox::empty(),
+                line_box: BoundingBox::empty(),
+                column_rules: Vec::new(),
+            }],
+        };
+        let json = plan.to_json().unwrap();
+        assert_that(&LayoutPlan::from_json(&json)).is_ok().is_equal_to(plan.clone());
+        assert_that(&plan.has_emphasis()).is_true();
+    }
+
+    #[test]
+    fn rejects_bad_json() {
+        assert_that(&LayoutPlan::from_json("{\"width\": 10}")).is_err();
+    }
+}
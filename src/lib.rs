@@ -1,44 +1,202 @@
-#![feature(test)]
+#![cfg_attr(test, feature(test))]
 
 #[cfg(test)]
 extern crate test;
 
-use crate::error::Result;
-use crate::font::load_font;
-use crate::metrics::Metrics;
-use crate::options::PoetryWallOptions;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::apply::apply_wallpaper;
+use crate::dimension::Dimension;
+use crate::error::{PoetryWallError, Result};
+use crate::font::{load_font, FontFamily};
+use crate::naming::{sized_file_name, DEFAULT_OUTPUT_TEMPLATE};
+use crate::options::{PoetryWallOptions, Sink, Source};
+use crate::pagination::page_file_name;
 use crate::poem::Poem;
-use crate::image_utils::{create_image, render_glyphs};
+use crate::preview::show_preview;
+use crate::image_utils::{print_image, save_image, Image};
+use crate::layout::LayoutPlan;
+use crate::wall::{draw_layout_plan, load_family, PoetryWall};
 
+pub mod alignment;
+pub mod apply;
+pub mod batch;
 pub mod bounding_box;
+pub mod builtin;
 pub mod color;
+pub mod columns;
+pub mod config;
 pub mod dimension;
 pub mod error;
 pub mod font;
 pub mod image_utils;
+pub mod layout;
+pub mod markup;
 pub mod metrics;
+pub mod naming;
 pub mod options;
+pub mod overlay;
+pub mod pagination;
+pub mod placement;
 pub mod poem;
+pub mod preview;
+pub mod rotation;
+pub mod shaping;
+pub mod solar;
+pub mod spacing;
+pub mod stanza;
+pub mod typography;
+pub mod units;
+pub mod wall;
+pub mod watch;
 
-// TODO: refactor to use interfaces and make more testable
-
+/// Renders the poem in the options to the files they name. To render in
+/// memory instead, use `PoetryWall`.
 pub fn create_poetry_wall(options: &PoetryWallOptions) -> Result<()> {
-    let poem = Poem::from_file(&options.poem_file)?;
-    let font = load_font(&options.font_file)?;
-    let metrics = Metrics::compute_metrics(options, &poem, font);
-    let glyphs = metrics.create_glyphs(poem.lines());
-    let background = options.background.srgb();
-
-    let mut image = create_image(
-        options.dimensions.width,
-        options.dimensions.height,
-        background.red,
-        background.green,
-        background.blue,
-    );
-    render_glyphs(&mut image, &glyphs, &options.color, &options.background);
-
-    image.save(&options.output_file)?;
+    if options.apply && !matches!(options.output, Some(Sink::File(_))) {
+        return Err(PoetryWallError::InvalidMissingOption(String::from(
+            "apply: the wallpaper has to be written to a file",
+        )));
+    }
+    if let Some(layout_file) = &options.layout_file {
+        let plan = LayoutPlan::from_file(layout_file)?;
+        let images = render_layout_plan(&plan, options)?;
+        let wallpaper = wallpaper(options.output.as_ref(), Dimension::new(plan.width, plan.height), images.len());
+        write_poetry_wall(&plan, images, options, options.output.as_ref(), options.emit_layout.as_deref())?;
+        if options.apply {
+            apply_wallpaper(wallpaper.as_slice(), options.apply_command.as_deref())?;
+        }
+        return Ok(());
+    }
+
+    // The poem and font are the same at every size, so they're only loaded once.
+    let mut wall = load_poetry_wall(options)?;
+    let template = match &options.output_template {
+        Some(template) => Some(template.as_str()),
+        None if options.dimensions.len() > 1 => Some(DEFAULT_OUTPUT_TEMPLATE),
+        None => None,
+    };
+    if template.is_some() && options.output == Some(Sink::Stdout) {
+        return Err(PoetryWallError::InvalidMissingOption(String::from(
+            "output: more than one size can't be written to stdout",
+        )));
+    }
+    let mut wallpapers = Vec::new();
+    for &dimensions in &options.dimensions {
+        let plan = wall.plan(dimensions);
+        let (output, emit_layout) = match template {
+            Some(template) => (
+                options.output.as_ref().and_then(Sink::file).map(|output_file| {
+                    Sink::File(sized_file_name(output_file, template, &dimensions, options.format))
+                }),
+                options.emit_layout.as_ref().map(|emit_layout| {
+                    sized_file_name(emit_layout, template, &dimensions, None).with_extension("json")
+                }),
+            ),
+            None => (options.output.clone(), options.emit_layout.clone()),
+        };
+        // Without an output or a preview, only the plan was wanted.
+        let images = if output.is_none() && options.preview.is_none() {
+            Vec::new()
+        } else {
+            wall.draw(&plan)
+        };
+        wallpapers.extend(wallpaper(output.as_ref(), dimensions, images.len()));
+        write_poetry_wall(&plan, images, options, output.as_ref(), emit_layout.as_deref())?;
+    }
+    if options.apply {
+        apply_wallpaper(&wallpapers, options.apply_command.as_deref())?;
+    }
+
+    Ok(())
+}
+
+/// The file to use as the wallpaper, if the images went to one: the image,
+/// or the first of a series.
+fn wallpaper(output: Option<&Sink>, dimensions: Dimension, pages: usize) -> Option<(Dimension, PathBuf)> {
+    let output_file = output?.file()?;
+    let file = if pages > 1 { page_file_name(output_file, 1) } else { output_file.to_path_buf() };
+    Some((dimensions, file))
+}
+
+/// Lays out the poem at the first of its sizes, working out everything but
+/// the pixels.
+pub fn plan_poetry_wall(options: &PoetryWallOptions) -> Result<LayoutPlan> {
+    let dimensions = options.dimensions.first().copied().ok_or_else(|| {
+        PoetryWallError::InvalidMissingOption(String::from("dimensions"))
+    })?;
+    Ok(load_poetry_wall(options)?.plan(dimensions))
+}
+
+/// Draws each page of a plan in the colors from the options.
+pub fn render_layout_plan(plan: &LayoutPlan, options: &PoetryWallOptions) -> Result<Vec<Image>> {
+    let mut family = if plan.has_emphasis() {
+        FontFamily::discover(&plan.font)?
+    } else {
+        FontFamily::new(load_font(&plan.font)?)
+    };
+    family.set_variations(&plan.variations.parse()?)?;
+    let mut options = options.clone();
+    options.resolve_palette();
+    Ok(draw_layout_plan(plan, &mut family, &options))
+}
+
+/// Reads the poem and font from the files in the options, or the poem from
+/// standard input.
+pub(crate) fn load_poetry_wall(options: &PoetryWallOptions) -> Result<PoetryWall> {
+    let poem = match &options.poem {
+        Some(Source::Stdin) => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text.parse()?
+        }
+        Some(Source::File(poem_file)) => Poem::from_file(poem_file)?,
+        None => return Err(PoetryWallError::InvalidMissingOption(String::from("poem"))),
+    };
+    let family = load_family(options, &poem)?;
+    PoetryWall::new(poem, family, options.clone())
+}
+
+/// Writes out the plan and its images, as the options ask.
+pub(crate) fn write_poetry_wall(
+    plan: &LayoutPlan,
+    images: Vec<Image>,
+    options: &PoetryWallOptions,
+    output: Option<&Sink>,
+    emit_layout: Option<&Path>,
+) -> Result<()> {
+    if let Some(emit_layout) = emit_layout {
+        plan.write(emit_layout)?;
+    }
+    if let Some(preview) = options.preview {
+        if output == Some(&Sink::Stdout) {
+            return Err(PoetryWallError::InvalidMissingOption(String::from(
+                "preview: it's shown on stdout, so the output can't go there too",
+            )));
+        }
+        for image in &images {
+            show_preview(image, preview)?;
+        }
+    }
+
+    match output {
+        None => {}
+        Some(Sink::Stdout) if images.len() == 1 => print_image(&images[0], options.format, options.dpi)?,
+        Some(Sink::Stdout) => {
+            return Err(PoetryWallError::InvalidMissingOption(String::from(
+                "output: a series of pages can't be written to stdout",
+            )));
+        }
+        Some(Sink::File(output_file)) if images.len() == 1 => {
+            save_image(&images[0], output_file, options.format, options.dpi)?
+        }
+        Some(Sink::File(output_file)) => {
+            for (i, image) in images.iter().enumerate() {
+                save_image(image, page_file_name(output_file, i + 1), options.format, options.dpi)?;
+            }
+        }
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
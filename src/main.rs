@@ -1,29 +1,113 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use clap::{app_from_crate, Arg, ArgMatches, crate_authors, crate_description, crate_name,
-           crate_version, value_t};
+use chrono::{DateTime, FixedOffset, Utc};
+use clap::{app_from_crate, Arg, ArgMatches, crate_authors, crate_description, crate_name, crate_version,
+           SubCommand};
+use toml::Value;
 
+use poetry_wall::alignment::Alignment;
+use poetry_wall::batch::{batch_jobs, find_poems, run_batch};
+use poetry_wall::builtin::DEFAULT_FONT;
+use poetry_wall::color::Color;
+use poetry_wall::columns::Columns;
+use poetry_wall::config::Config;
 use poetry_wall::create_poetry_wall;
 use poetry_wall::dimension::Dimension;
 use poetry_wall::error::{PoetryWallError, Result};
-use poetry_wall::options::PoetryWallOptions;
+use poetry_wall::font::FontVariations;
+use poetry_wall::image_utils::ImageFormat;
+use poetry_wall::options::{PoetryWallOptions, Sink, Source};
+use poetry_wall::placement::VerticalPlacement;
+use poetry_wall::preview::Preview;
+use poetry_wall::rotation::{default_history_file, run_daemon, Schedule};
+use poetry_wall::shaping::FontFeatures;
+use poetry_wall::solar::{Palette, Solar};
+use poetry_wall::spacing::{LineHeight, Spacing};
+use poetry_wall::typography::Language;
+use poetry_wall::units::{Length, DEFAULT_DPI};
+use poetry_wall::watch::{watch, watched_files};
 
 fn main() -> Result<()> {
-    let options = parse_options()?;
-    create_poetry_wall(&options)
+    let matches = parse_args();
+    if matches.is_present("watch") {
+        if let Some(command) = matches.subcommand_name() {
+            return Err(PoetryWallError::InvalidMissingOption(format!(
+                "watch: it only works for a single poem, not with {}",
+                command
+            )));
+        }
+        if matches.value_of("poem") == Some("-") {
+            return Err(PoetryWallError::InvalidMissingOption(String::from(
+                "watch: stdin can only be read once, so the poem has to be a file",
+            )));
+        }
+        return watch_poem(&matches);
+    }
+    let (options, settings) = resolve_options(&matches)?;
+
+    if matches.is_present("print-config") {
+        print!("{}", settings.to_toml()?);
+        return Ok(());
+    }
+    match matches.subcommand() {
+        ("batch", Some(batch)) => run_batch_command(batch, &options),
+        ("daemon", Some(daemon)) => run_daemon_command(daemon, &options),
+        _ => render(&options),
+    }
+}
+
+/// Reads the options from the command line and the configuration.
+fn resolve_options<'a>(matches: &'a ArgMatches<'a>) -> Result<(PoetryWallOptions, Settings<'a>)> {
+    let config = Config::load()?.settings(matches.value_of("profile"))?;
+    let settings = Settings::new(matches, config);
+    let options = read_options(&settings)?;
+    settings.check_config()?;
+    Ok((options, settings))
 }
 
-fn parse_options() -> Result<PoetryWallOptions> {
-    let matches = app_from_crate!()
+fn render(options: &PoetryWallOptions) -> Result<()> {
+    if options.poem.is_none() && options.layout_file.is_none() {
+        return Err(PoetryWallError::InvalidMissingOption(String::from("poem")));
+    }
+    if options.output.is_none() && options.emit_layout.is_none() && options.preview.is_none() {
+        return Err(PoetryWallError::InvalidMissingOption(String::from("output")));
+    }
+    create_poetry_wall(options)
+}
+
+/// Renders whenever the poem, font or configuration changes, reporting
+/// errors instead of stopping. The configuration is read again each time.
+fn watch_poem(matches: &ArgMatches) -> Result<()> {
+    let mut files = Config::paths();
+    watch(|| {
+        let result = resolve_options(matches).and_then(|(options, _)| {
+            files = watched_files(&options);
+            render(&options).map(|()| options.output)
+        });
+        match result {
+            Ok(Some(output)) => eprintln!("Rendered {}", output),
+            Ok(None) => eprintln!("Rendered"),
+            Err(err) => eprintln!("Error: {}", err),
+        }
+        files.clone()
+    })
+}
+
+fn parse_args() -> ArgMatches<'static> {
+    app_from_crate!()
         .arg(
             Arg::with_name("poem")
                 .short("p")
                 .long("poem")
-                .help("The poem to render in a markdown file.")
+                .help("The poem to render in a markdown file, or '-' to read it from stdin.")
                 .value_name("MARKDOWN_FILE")
                 .takes_value(true)
-                .required(true)
+                .required(false)
         )
         .arg(
             Arg::with_name("color")
@@ -45,42 +129,226 @@ fn parse_options() -> Result<PoetryWallOptions> {
                 .required(false)
                 .default_value("black")
         )
+        .arg(
+            Arg::with_name("latitude")
+                .long("latitude")
+                .help("With --longitude, pick the day or night palette by whether the sun is up \
+                            here, blending them through twilight. It's in degrees north.")
+                .value_name("DEGREES")
+                .takes_value(true)
+                .required(false)
+                .allow_hyphen_values(true)
+        )
+        .arg(
+            Arg::with_name("longitude")
+                .long("longitude")
+                .help("Where to follow the sun, in degrees east.")
+                .value_name("DEGREES")
+                .takes_value(true)
+                .required(false)
+                .allow_hyphen_values(true)
+        )
+        .arg(
+            Arg::with_name("day-palette")
+                .long("day-palette")
+                .help("The text color and background while the sun is up.")
+                .value_name("COLOR/BACKGROUND")
+                .takes_value(true)
+                .required(false)
+                .default_value("black/white")
+        )
+        .arg(
+            Arg::with_name("night-palette")
+                .long("night-palette")
+                .help("The text color and background at night. It defaults to --color and \
+                            --background.")
+                .value_name("COLOR/BACKGROUND")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("time")
+                .long("time")
+                .help("Pick the palette for this time, like '2024-06-21T21:30:00+01:00', instead \
+                            of now.")
+                .value_name("TIME")
+                .takes_value(true)
+                .required(false)
+        )
         .arg(
             Arg::with_name("font")
                 .short("f")
                 .long("font")
-                .help("The TTF font to use rendering the poem.")
+                .help("The TTF or OTF font to use rendering the poem. Instead of a file, this \
+                            can be one of the built-in fonts, 'builtin:serif' or 'builtin:mono'.")
                 .value_name("TTF_FONT")
                 .takes_value(true)
-                .required(true)
+                .required(false)
+                .default_value(DEFAULT_FONT)
+        )
+        .arg(
+            Arg::with_name("font-variation")
+                .long("font-variation")
+                .help("For variable fonts, a comma-separated list of axis settings, e.g., \
+                            'wght=350,opsz=24'. If 'opsz' isn't given, it's set from the final \
+                            font size.")
+                .value_name("AXES")
+                .takes_value(true)
+                .required(false)
         )
         .arg(
             Arg::with_name("max-font-size")
                 .short("F")
                 .long("max-font-size")
                 .help("The size of type to use rendering the poem. If there's not enough room, \
-                            it will be scaled down.")
-                .value_name("TTF_FONT")
+                            it will be scaled down. This is in pixels, or it can have a unit: \
+                            'pt', 'mm', 'in', or '%' of the image height.")
+                .value_name("SIZE")
                 .takes_value(true)
                 .required(false)
                 .default_value("72")
         )
+        .arg(
+            Arg::with_name("min-font-size")
+                .long("min-font-size")
+                .help("Instead of setting the type smaller than this, split the poem between \
+                            stanzas into a series of wallpapers that share the same size and \
+                            margins. They're written to the output name with a page number added, \
+                            e.g., 'wall-1.png', 'wall-2.png'. This takes the same units as \
+                            '--max-font-size'.")
+                .value_name("SIZE")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("continued")
+                .long("continued")
+                .help("A line to add to the bottom of every page of a series but the last, e.g., \
+                            '*continued*'.")
+                .value_name("TEXT")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("page-numbers")
+                .long("page-numbers")
+                .help("Put page numbers at the bottom of the pages of a series.")
+        )
+        .arg(
+            Arg::with_name("features")
+                .long("features")
+                .help("A comma-separated list of OpenType features to turn on or off, e.g., \
+                            'liga,-kern,onum'. Kerning and standard ligatures are on by default.")
+                .value_name("FEATURES")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("letter-spacing")
+                .long("letter-spacing")
+                .help("Extra space to add between letters, in ems. Negative values tighten the \
+                            text.")
+                .value_name("EMS")
+                .takes_value(true)
+                .required(false)
+                .allow_hyphen_values(true)
+                .default_value("0")
+        )
+        .arg(
+            Arg::with_name("word-spacing")
+                .long("word-spacing")
+                .help("Extra space to add between words, in ems, on top of the letter spacing.")
+                .value_name("EMS")
+                .takes_value(true)
+                .required(false)
+                .allow_hyphen_values(true)
+                .default_value("0")
+        )
+        .arg(
+            Arg::with_name("line-height")
+                .long("line-height")
+                .help("The distance between baselines. This is either a multiple of the font \
+                            size, like '1.4', or pixels, like '96px'. Both shrink with the text \
+                            if it has to be scaled down. If omitted, the font's own spacing is used.")
+                .value_name("HEIGHT")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("align")
+                .long("align")
+                .help("How to line up the lines: 'left', 'center' or 'right'.")
+                .value_name("ALIGNMENT")
+                .takes_value(true)
+                .required(false)
+                .default_value("left")
+        )
+        .arg(
+            Arg::with_name("hanging-punctuation")
+                .long("hanging-punctuation")
+                .help("How far quotes and dashes at the start of a line hang into the margin, as a \
+                            fraction of their width, so the letters line up. With center or right \
+                            alignment, punctuation at the end of a line hangs too. '1' hangs them \
+                            all the way.")
+                .value_name("FRACTION")
+                .takes_value(true)
+                .required(false)
+                .default_value("0")
+        )
+        .arg(
+            Arg::with_name("smart-typography")
+                .long("smart-typography")
+                .help("Use curly quotes and apostrophes, dashes for '--' and '---', and ellipses for \
+                            '...'. A poem can turn this on or off with 'typography: on' or \
+                            'typography: off' in its front matter.")
+        )
+        .arg(
+            Arg::with_name("lang")
+                .long("lang")
+                .help("The language whose quotation marks to use: 'en', 'fr' or 'de'. A poem can \
+                            set this with 'lang:' in its front matter.")
+                .value_name("LANG")
+                .takes_value(true)
+                .required(false)
+                .default_value("en")
+        )
+        .arg(
+            Arg::with_name("typography-report")
+                .long("typography-report")
+                .help("Print each typographic substitution that's made to stderr.")
+        )
         .arg(
             Arg::with_name("dimensions")
                 .short("d")
                 .long("dimensions")
-                .help("The size of image to create, in the form 'WIDTHxHEIGHT'.")
-                .value_name("DIMENSION")
+                .help("The size of image to create, in the form 'WIDTHxHEIGHT'. Each side is in \
+                            pixels, or in 'pt', 'mm' or 'in' at the '--dpi', e.g., '210mmx297mm'. \
+                            This can be a comma-separated list of sizes and the presets 'phones', \
+                            'tablets' and 'desktops' to render them all, named by \
+                            '--output-template'.")
+                .value_name("DIMENSIONS")
                 .takes_value(true)
                 .required(false)
                 .default_value("1920x1080")
         )
+        .arg(
+            Arg::with_name("dpi")
+                .long("dpi")
+                .help("The resolution to size 'pt', 'mm' and 'in' at, which is also written into \
+                            the PNG so it prints at the right size. Without it, physical units are \
+                            sized at 96 dpi and no resolution is written.")
+                .value_name("DPI")
+                .takes_value(true)
+                .required(false)
+        )
         .arg(
             Arg::with_name("left")
                 .short("l")
                 .long("left")
-                .help("The size of the left margin. If omitted, it's computed.")
-                .value_name("NUMBER")
+                .help("The size of the left margin. If omitted, it's computed. This is in pixels, \
+                            or it can have a unit: 'pt', 'mm', 'in', 'em' of the final type size, or \
+                            '%' of the image width.")
+                .value_name("LENGTH")
                 .takes_value(true)
                 .required(false)
         )
@@ -88,34 +356,303 @@ fn parse_options() -> Result<PoetryWallOptions> {
             Arg::with_name("top")
                 .short("t")
                 .long("top")
-                .help("The size of the top margin. If omitted, it's computed.")
-                .value_name("NUMBER")
+                .help("The size of the top margin. If omitted, it's computed. This takes the same \
+                            units as '--left', with '%' of the image height.")
+                .value_name("LENGTH")
                 .takes_value(true)
                 .required(false)
         )
+        .arg(
+            Arg::with_name("vertical-placement")
+                .long("vertical-placement")
+                .help("Where to put the poem vertically if '--top' isn't given: 'third' puts a \
+                            third of the space above it, 'golden' divides the space in the golden \
+                            ratio, 'center' centers it, and 'optical' centers the block from the \
+                            first line's capitals to the last line's baseline, a little above the \
+                            middle.")
+                .value_name("PLACEMENT")
+                .takes_value(true)
+                .required(false)
+                .default_value("third")
+        )
+        .arg(
+            Arg::with_name("baseline-grid")
+                .long("baseline-grid")
+                .help("Snap the first baseline to a grid of this many pixels from the top of the \
+                            image, so wallpapers from different poems line up.")
+                .value_name("PIXELS")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("columns")
+                .long("columns")
+                .help("How many columns to set the poem in, or 'auto' to use whichever number \
+                            lets the type be the biggest. Columns break between stanzas where \
+                            they can.")
+                .value_name("COUNT")
+                .takes_value(true)
+                .required(false)
+                .default_value("1")
+        )
+        .arg(
+            Arg::with_name("column-gap")
+                .long("column-gap")
+                .help("The space between columns, in ems.")
+                .value_name("EMS")
+                .takes_value(true)
+                .required(false)
+                .default_value("2")
+        )
+        .arg(
+            Arg::with_name("column-rule")
+                .long("column-rule")
+                .help("Draw a rule this many pixels wide in the text color between columns.")
+                .value_name("PIXELS")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("debug-overlay")
+                .long("debug-overlay")
+                .help("Draw the safe area (cyan), the margins (magenta), each line's ascent \
+                            (green), baseline (red) and descent (blue), and the line box (orange) \
+                            and ink box (yellow) over the poem, and print the font size and \
+                            margins that were chosen.")
+        )
         .arg(
             Arg::with_name("output")
                 .short("o")
                 .long("output")
-                .help("The output file to be created as a PNG.")
+                .help("The output file to be created as a PNG, or '-' to write to stdout.")
                 .value_name("PNG_FILE")
                 .takes_value(true)
-                .required(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("The format to write: 'png', 'jpeg' or 'bmp'. This is needed to write to \
+                            stdout, and otherwise it comes from the output's extension.")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("preview")
+                .long("preview")
+                .help("Show the image in the terminal, scaled to fit it. This works over SSH, and \
+                            without an output file nothing is written.")
+        )
+        .arg(
+            Arg::with_name("preview-protocol")
+                .long("preview-protocol")
+                .help("How to draw the preview: 'kitty' graphics, 'sixel', truecolor half 'blocks', \
+                            or 'auto' to pick one for the terminal.")
+                .value_name("PROTOCOL")
+                .takes_value(true)
+                .required(false)
+                .default_value("auto")
+        )
+        .arg(
+            Arg::with_name("apply")
+                .long("apply")
+                .help("Set the image as the desktop wallpaper on GNOME, KDE Plasma, sway, Hyprland \
+                            (with hyprpaper) or X11 (with feh). Each monitor gets the image of its \
+                            size, if there's one.")
+        )
+        .arg(
+            Arg::with_name("apply-command")
+                .long("apply-command")
+                .help("With --apply, set the wallpaper with this shell command instead, where \
+                            '{file}' is the image and '{width}' and '{height}' are its size.")
+                .value_name("COMMAND")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .help("Use the settings from this profile in 'poetry-wall.toml', as well as the ones \
+                            for every run. The file is read from the user's config directory, \
+                            e.g., '~/.config/poetry-wall/', and then the current directory.")
+                .value_name("NAME")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .help("Keep running, and render again whenever the poem, the font files or \
+                            'poetry-wall.toml' change. Errors are printed, and it carries on.")
+        )
+        .arg(
+            Arg::with_name("print-config")
+                .long("print-config")
+                .help("Print the settings from the defaults, 'poetry-wall.toml' and the command \
+                            line, as a profile, and exit.")
+        )
+        .arg(
+            Arg::with_name("output-template")
+                .long("output-template")
+                .help("How to name the image for each size, next to '--output'. '{stem}' and \
+                            '{ext}' come from the output name, and '{width}' and '{height}' from \
+                            the size. With more than one size, this defaults to \
+                            '{stem}-{width}x{height}.{ext}'.")
+                .value_name("TEMPLATE")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("emit-layout")
+                .long("emit-layout")
+                .help("Write the layout plan, with every glyph's position, to this file as JSON. \
+                            Without '--output', nothing is rendered.")
+                .value_name("JSON_FILE")
+                .takes_value(true)
+                .required(false)
+        )
+        .arg(
+            Arg::with_name("layout")
+                .long("layout")
+                .help("Render a layout plan written by '--emit-layout', or by another tool, \
+                            instead of laying out a poem.")
+                .value_name("JSON_FILE")
+                .takes_value(true)
+                .required(false)
+        )
+        .subcommand(
+            SubCommand::with_name("batch")
+                .about("Renders many poems, at every size in '--dimensions', in parallel. The other \
+                            options before 'batch' apply to all of them.")
+                .arg(
+                    Arg::with_name("poems")
+                        .help("The poems to render: markdown files, directories of them, or glob \
+                                    patterns like 'poems/*.md'.")
+                        .value_name("POEMS")
+                        .multiple(true)
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("output-dir")
+                        .short("o")
+                        .long("output-dir")
+                        .help("The directory to write the images to, named after the poems.")
+                        .value_name("DIRECTORY")
+                        .takes_value(true)
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Render every image, even the ones newer than their poem and font.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("daemon")
+                .about("Keeps running, and renders a poem from a library on a schedule, without \
+                            repeating one until they've all been shown. The options before \
+                            'daemon' apply to each, so '--output', '--profile' and '--apply' go \
+                            there.")
+                .arg(
+                    Arg::with_name("library")
+                        .help("The directory of markdown poems to choose from.")
+                        .value_name("LIBRARY")
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("schedule")
+                        .long("schedule")
+                        .help("When to show the next poem: 'hourly', 'daily' at midnight, or a cron \
+                                    expression like '0 7 * * *', in local time.")
+                        .value_name("SCHEDULE")
+                        .takes_value(true)
+                        .default_value("daily")
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .help("The number the choices are made from, so the same seed and history \
+                                    always show the same poems. By default a new history gets one \
+                                    from the clock.")
+                        .value_name("SEED")
+                        .takes_value(true)
+                        .required(false)
+                )
+                .arg(
+                    Arg::with_name("history")
+                        .long("history")
+                        .help("The file to keep the poems that have been shown in. By default it's \
+                                    'poetry-wall/history.json' in your data directory.")
+                        .value_name("JSON_FILE")
+                        .takes_value(true)
+                        .required(false)
+                )
         )
-        .get_matches();
+        .get_matches()
+}
 
-    let poem_file: PathBuf = read_name_value(&matches, "poem")?;
-    let font_file = read_name_value(&matches, "font")?;
-    let output_file = read_name_value(&matches, "output")?;
-    let color = read_name_value(&matches, "color")?;
-    let background = read_name_value(&matches, "background")?;
-    let font_size: f32 = read_name_value(&matches, "max-font-size")?;
-    let dimensions: Dimension = read_name_value(&matches, "dimensions")?;
-    let top: Option<u32> = matches.value_of("top").map(|v| v.parse()).transpose()?;
-    let left: Option<u32> = matches.value_of("left").map(|v| v.parse()).transpose()?;
+fn read_options(settings: &Settings) -> Result<PoetryWallOptions> {
+    let poem = settings.value_of("poem").map(|poem| Source::from_arg(&poem));
+    let font_file: PathBuf = read_name_value(settings, "font")?;
+    let output = settings.value_of("output").map(|output| Sink::from_arg(&output));
+    let format: Option<ImageFormat> = read_optional_value(settings, "format")?;
+    let preview: Preview = read_name_value(settings, "preview-protocol")?;
+    let color: Color = read_name_value(settings, "color")?;
+    let background: Color = read_name_value(settings, "background")?;
+    let latitude: Option<f64> = read_optional_value(settings, "latitude")?;
+    let longitude: Option<f64> = read_optional_value(settings, "longitude")?;
+    let day_palette: Palette = read_name_value(settings, "day-palette")?;
+    let night_palette: Option<Palette> = read_optional_value(settings, "night-palette")?;
+    let solar = match (latitude, longitude) {
+        (Some(latitude), Some(longitude)) => {
+            let night_palette = night_palette.unwrap_or_else(|| Palette::new(color.clone(), background.clone()));
+            Some(Solar::new(latitude, longitude, day_palette, night_palette)?)
+        }
+        (None, None) => None,
+        (Some(_), None) => return Err(PoetryWallError::InvalidMissingOption(String::from("longitude"))),
+        (None, Some(_)) => return Err(PoetryWallError::InvalidMissingOption(String::from("latitude"))),
+    };
+    let time: Option<DateTime<FixedOffset>> = read_optional_value(settings, "time")?;
+    let dpi: Option<f32> = read_optional_value(settings, "dpi")?;
+    if let Some(dpi) = dpi.filter(|&dpi| dpi <= 0.0) {
+        return Err(PoetryWallError::InvalidMissingOption(format!("dpi: {}", dpi)));
+    }
+    let resolution = dpi.unwrap_or(DEFAULT_DPI);
+    let dimensions = Dimension::parse_list(&settings.value_of("dimensions").unwrap_or_default(), resolution)?;
+    let font_size: Length = read_name_value(settings, "max-font-size")?;
+    // Percentages are worked out for each size, but ems are never allowed.
+    font_size.to_absolute_pixels(resolution, 0.0)?;
+    let top: Option<Length> = read_optional_value(settings, "top")?;
+    let left: Option<Length> = read_optional_value(settings, "left")?;
+    let features: Option<FontFeatures> = read_optional_value(settings, "features")?;
+    let variations: Option<FontVariations> =
+        read_optional_value(settings, "font-variation")?;
+    let letter_spacing: f32 = read_name_value(settings, "letter-spacing")?;
+    let word_spacing: f32 = read_name_value(settings, "word-spacing")?;
+    let line_height: Option<LineHeight> =
+        read_optional_value(settings, "line-height")?;
+    let language: Language = read_name_value(settings, "lang")?;
+    let alignment: Alignment = read_name_value(settings, "align")?;
+    let hanging_punctuation: f32 = read_name_value(settings, "hanging-punctuation")?;
+    let vertical_placement: VerticalPlacement = read_name_value(settings, "vertical-placement")?;
+    let baseline_grid: Option<f32> = read_optional_value(settings, "baseline-grid")?;
+    if let Some(grid) = baseline_grid.filter(|&grid| grid <= 0.0) {
+        return Err(PoetryWallError::InvalidMissingOption(format!("baseline-grid: {}", grid)));
+    }
+    let columns: Columns = read_name_value(settings, "columns")?;
+    let column_gap: f32 = read_name_value(settings, "column-gap")?;
+    if column_gap < 0.0 || !column_gap.is_finite() {
+        return Err(PoetryWallError::InvalidMissingOption(format!("column-gap: {}", column_gap)));
+    }
+    let column_rule: Option<f32> = read_optional_value(settings, "column-rule")?;
+    let min_font_size: Option<Length> = read_optional_value(settings, "min-font-size")?;
+    if let Some(min_font_size) = min_font_size {
+        min_font_size.to_absolute_pixels(resolution, 0.0)?;
+    }
 
-    Ok(PoetryWallOptions::new(
-        poem_file,
+    let mut options = PoetryWallOptions::new(
+        poem,
         font_file,
         font_size,
         color,
@@ -123,10 +660,154 @@ fn parse_options() -> Result<PoetryWallOptions> {
         dimensions,
         top,
         left,
-        output_file,
-    ))
+        output,
+    );
+    options.output_template = settings.value_of("output-template");
+    options.solar = solar;
+    options.time = time.map(|time| time.with_timezone(&Utc));
+    options.format = format;
+    options.preview = if settings.flag("preview")? { Some(preview) } else { None };
+    options.apply_command = settings.value_of("apply-command");
+    options.apply = settings.flag("apply")?;
+    options.features = features.unwrap_or_default();
+    options.variations = variations.unwrap_or_default();
+    options.spacing = Spacing::new(letter_spacing, word_spacing, line_height.unwrap_or_default());
+    options.typography = settings.flag("smart-typography")?;
+    options.language = language;
+    options.typography_report = settings.flag("typography-report")?;
+    options.alignment = alignment;
+    options.hanging_punctuation = hanging_punctuation;
+    options.vertical_placement = vertical_placement;
+    options.baseline_grid = baseline_grid;
+    options.columns = columns;
+    options.column_gap = column_gap;
+    options.column_rule = column_rule;
+    options.min_font_size = min_font_size;
+    options.continued_marker = settings.value_of("continued");
+    options.page_numbers = settings.flag("page-numbers")?;
+    options.emit_layout = settings.value_of("emit-layout").map(PathBuf::from);
+    options.layout_file = settings.value_of("layout").map(PathBuf::from);
+    options.debug_overlay = settings.flag("debug-overlay")?;
+    options.dpi = dpi;
+    options.command_line = settings.command_line();
+
+    Ok(options)
+}
+
+fn run_batch_command(matches: &ArgMatches, options: &PoetryWallOptions) -> Result<()> {
+    let patterns: Vec<&str> = matches.values_of("poems").map(Iterator::collect).unwrap_or_default();
+    let poems = find_poems(&patterns)?;
+    let output_dir = PathBuf::from(matches.value_of("output-dir").unwrap_or_default());
+    let jobs = batch_jobs(&poems, &output_dir, options)?;
+    fs::create_dir_all(&output_dir)?;
+
+    let report = run_batch(jobs, options, matches.is_present("force"));
+    for (job, err) in report.failures() {
+        eprintln!("{} ({}x{}): {}", job.poem_file.display(), job.dimensions.width, job.dimensions.height, err);
+    }
+    println!("{}", report);
+
+    match report.failures().count() {
+        0 => Ok(()),
+        count => Err(PoetryWallError::BatchError(format!("{} of {} images failed", count, report.results.len()))),
+    }
+}
+
+fn run_daemon_command(matches: &ArgMatches, options: &PoetryWallOptions) -> Result<()> {
+    let library = PathBuf::from(matches.value_of("library").unwrap_or_default());
+    let schedule: Schedule = matches.value_of("schedule").unwrap_or_default().parse()?;
+    let seed = matches
+        .value_of("seed")
+        .map(|seed| seed.parse().map_err(|_| PoetryWallError::InvalidMissingOption(format!("seed: {}", seed))))
+        .transpose()?;
+    let history_file = matches
+        .value_of("history")
+        .map(PathBuf::from)
+        .or_else(default_history_file)
+        .ok_or_else(|| PoetryWallError::InvalidMissingOption(String::from("history")))?;
+    run_daemon(&library, &schedule, &history_file, seed, options)
+}
+
+fn read_name_value<T>(settings: &Settings, name: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    read_optional_value(settings, name)?
+        .ok_or_else(|| PoetryWallError::InvalidMissingOption(String::from(name)))
 }
 
-fn read_name_value<T: FromStr>(matches: &ArgMatches, name: &str) -> Result<T> {
-    value_t!(matches, name, T).map_err(|message| PoetryWallError::InvalidMissingOption(format!("{}: {}", &name, &message)))
-}
\ No newline at end of file
+fn read_optional_value<T>(settings: &Settings, name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    settings
+        .value_of(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|err| PoetryWallError::InvalidMissingOption(format!("{}: {}: {}", name, value, err)))
+        })
+        .transpose()
+}
+
+/// Where each option's value comes from: the command line if it's given
+/// there, then the configuration, and then the option's default.
+struct Settings<'a> {
+    matches: &'a ArgMatches<'a>,
+    config: BTreeMap<String, String>,
+    /// Every option that's been read, with what it came to.
+    resolved: RefCell<BTreeMap<String, Option<Value>>>,
+}
+
+impl<'a> Settings<'a> {
+    fn new(matches: &'a ArgMatches<'a>, config: BTreeMap<String, String>) -> Self {
+        Settings { matches, config, resolved: RefCell::new(BTreeMap::new()) }
+    }
+
+    fn value_of(&self, name: &str) -> Option<String> {
+        let value = match self.config.get(name) {
+            Some(value) if self.matches.occurrences_of(name) == 0 => Some(value.clone()),
+            _ => self.matches.value_of(name).map(String::from),
+        };
+        self.resolved.borrow_mut().insert(String::from(name), value.clone().map(Value::String));
+        value
+    }
+
+    fn flag(&self, name: &str) -> Result<bool> {
+        let present = match self.config.get(name) {
+            Some(value) if !self.matches.is_present(name) => value
+                .parse()
+                .map_err(|_| PoetryWallError::ConfigError(format!("{} should be true or false", name)))?,
+            _ => self.matches.is_present(name),
+        };
+        self.resolved.borrow_mut().insert(String::from(name), Some(Value::Boolean(present)));
+        Ok(present)
+    }
+
+    /// The options that were given on the command line.
+    fn command_line(&self) -> BTreeSet<String> {
+        let resolved = self.resolved.borrow();
+        resolved.keys().filter(|name| self.matches.occurrences_of(name) > 0).cloned().collect()
+    }
+
+    /// Fails if the configuration has settings for options that don't exist.
+    fn check_config(&self) -> Result<()> {
+        let resolved = self.resolved.borrow();
+        match self.config.keys().find(|name| !resolved.contains_key(*name)) {
+            Some(name) => Err(PoetryWallError::ConfigError(format!("unknown setting {}", name))),
+            None => Ok(()),
+        }
+    }
+
+    /// The resolved options, in the form of a profile.
+    fn to_toml(&self) -> Result<String> {
+        let resolved = self.resolved.borrow();
+        let resolved: BTreeMap<&String, &Value> = resolved
+            .iter()
+            .filter_map(|(name, value)| value.as_ref().map(|value| (name, value)))
+            .collect();
+        toml::to_string(&resolved).map_err(|err| PoetryWallError::ConfigError(err.to_string()))
+    }
+}
@@ -0,0 +1,214 @@
+use crate::font::FontStyle;
+
+/// A run of text that's all set in the same style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: FontStyle,
+}
+
+impl Span {
+    pub fn new<S: Into<String>>(text: S, style: FontStyle) -> Self {
+        Span {
+            text: text.into(),
+            style,
+        }
+    }
+}
+
+pub type StyledLine = Vec<Span>;
+
+enum Token {
+    Text(String),
+    Delimiter {
+        marker: char,
+        count: usize,
+        can_open: bool,
+        can_close: bool,
+    },
+}
+
+/// What a matched delimiter run does to the style.
+#[derive(Clone, Copy, PartialEq)]
+enum Emphasis {
+    Open,
+    Close,
+}
+
+/// Parses the inline Markdown emphasis in one line: `*italic*`, `_italic_`,
+/// `**bold**`, `__bold__` and `***both***`. Delimiters follow CommonMark's
+/// flanking rules, so `2 * 3` and `snake_case` stay as they are. Runs that
+/// don't have a partner on the same line are left in the text.
+pub fn parse_emphasis(line: &str) -> StyledLine {
+    let tokens = tokenize(line);
+    let emphasis = match_delimiters(&tokens);
+
+    let mut spans: StyledLine = Vec::new();
+    let mut bold = 0;
+    let mut italic = 0;
+    for (token, emphasis) in tokens.iter().zip(emphasis) {
+        let text = match (token, emphasis) {
+            (Token::Text(text), _) => text.clone(),
+            (Token::Delimiter { count, .. }, Some(Emphasis::Open)) => {
+                bold += count / 2;
+                italic += count % 2;
+                continue;
+            }
+            (Token::Delimiter { count, .. }, Some(Emphasis::Close)) => {
+                bold -= count / 2;
+                italic -= count % 2;
+                continue;
+            }
+            (Token::Delimiter { marker, count, .. }, None) => marker.to_string().repeat(*count),
+        };
+        let style = FontStyle::new(bold > 0, italic > 0);
+        match spans.last_mut() {
+            Some(span) if span.style == style => span.text.push_str(&text),
+            _ => spans.push(Span::new(text, style)),
+        }
+    }
+
+    if spans.is_empty() {
+        spans.push(Span::new("", FontStyle::Regular));
+    }
+    spans
+}
+
+fn tokenize(line: &str) -> Vec<Token> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && i + 1 < chars.len() && (chars[i + 1] == '*' || chars[i + 1] == '_') {
+            text.push(chars[i + 1]);
+            i += 2;
+        } else if c == '*' || c == '_' {
+            let start = i;
+            while i < chars.len() && chars[i] == c {
+                i += 1;
+            }
+            let before = if start > 0 { Some(chars[start - 1]) } else { None };
+            let after = chars.get(i).cloned();
+            let (can_open, can_close) = flanking(c, before, after);
+            if !text.is_empty() {
+                tokens.push(Token::Text(text.split_off(0)));
+            }
+            tokens.push(Token::Delimiter {
+                marker: c,
+                count: i - start,
+                can_open,
+                can_close,
+            });
+        } else {
+            text.push(c);
+            i += 1;
+        }
+    }
+
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    tokens
+}
+
+fn flanking(marker: char, before: Option<char>, after: Option<char>) -> (bool, bool) {
+    let is_space = |c: Option<char>| c.is_none_or(char::is_whitespace);
+    let is_punctuation = |c: Option<char>| c.is_some_and(|c| c.is_ascii_punctuation());
+
+    let left = !is_space(after) && (!is_punctuation(after) || is_space(before) || is_punctuation(before));
+    let right = !is_space(before) && (!is_punctuation(before) || is_space(after) || is_punctuation(after));
+
+    if marker == '_' {
+        (left && (!right || is_punctuation(before)), right && (!left || is_punctuation(after)))
+    } else {
+        (left, right)
+    }
+}
+
+/// Pairs up closing delimiter runs with the nearest opening run that uses the
+/// same marker and length.
+fn match_delimiters(tokens: &[Token]) -> Vec<Option<Emphasis>> {
+    let mut emphasis = vec![None; tokens.len()];
+    let mut openers: Vec<usize> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        if let Token::Delimiter {
+            marker,
+            count,
+            can_open,
+            can_close,
+        } = token
+        {
+            if *count > 3 {
+                continue;
+            }
+            let opener = if *can_close {
+                openers.iter().rposition(|&j| match &tokens[j] {
+                    Token::Delimiter { marker: m, count: n, .. } => m == marker && n == count,
+                    _ => false,
+                })
+            } else {
+                None
+            };
+            match opener {
+                Some(position) => {
+                    emphasis[openers[position]] = Some(Emphasis::Open);
+                    emphasis[i] = Some(Emphasis::Close);
+                    openers.truncate(position);
+                }
+                None if *can_open => openers.push(i),
+                None => {}
+            }
+        }
+    }
+
+    emphasis
+}
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use crate::font::FontStyle;
+    use crate::markup::{parse_emphasis, Span};
+
+    #[test]
+    fn leaves_plain_text_alone() {
+        assert_that(&parse_emphasis("I heard a Fly buzz – when I died –"))
+            .is_equal_to(vec![Span::new("I heard a Fly buzz – when I died –", FontStyle::Regular)]);
+    }
+
+    #[test]
+    fn reads_italic_and_bold() {
+        assert_that(&parse_emphasis("a *Fly* and __me__")).is_equal_to(vec![
+            Span::new("a ", FontStyle::Regular),
+            Span::new("Fly", FontStyle::Italic),
+            Span::new(" and ", FontStyle::Regular),
+            Span::new("me", FontStyle::Bold),
+        ]);
+    }
+
+    #[test]
+    fn reads_nested_emphasis() {
+        assert_that(&parse_emphasis("**the *King* be**")).is_equal_to(vec![
+            Span::new("the ", FontStyle::Bold),
+            Span::new("King", FontStyle::BoldItalic),
+            Span::new(" be", FontStyle::Bold),
+        ]);
+        assert_that(&parse_emphasis("***Storm***"))
+            .is_equal_to(vec![Span::new("Storm", FontStyle::BoldItalic)]);
+    }
+
+    #[test]
+    fn keeps_unmatched_delimiters() {
+        assert_that(&parse_emphasis("2 * 3 = *six"))
+            .is_equal_to(vec![Span::new("2 * 3 = *six", FontStyle::Regular)]);
+        assert_that(&parse_emphasis("snake_case_name"))
+            .is_equal_to(vec![Span::new("snake_case_name", FontStyle::Regular)]);
+        assert_that(&parse_emphasis(r"\*not\*"))
+            .is_equal_to(vec![Span::new("*not*", FontStyle::Regular)]);
+    }
+}
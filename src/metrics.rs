@@ -1,82 +1,636 @@
-use rusttype::{Font, point, Scale, VMetrics};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
+use ab_glyph::PxScale;
+use rustybuzz::Direction;
+
+use crate::alignment::{hangs_at_end, hangs_at_start, Alignment};
 use crate::bounding_box::BoundingBox;
-use crate::font::GlyphVec;
+use crate::builtin::builtin_name;
+use crate::columns::{Columns, MAX_AUTO_COLUMNS};
+use crate::dimension::Dimension;
+use crate::font::{FontFamily, FontStyle, GlyphVec, VMetrics};
+use crate::layout::{GlyphPlan, LayoutPlan, LinePlan, PagePlan};
+use crate::markup::{Span, StyledLine};
 use crate::options::PoetryWallOptions;
+use crate::pagination::add_continued_markers;
+use crate::placement::{snap_to_grid, VerticalExtent};
 use crate::poem::Poem;
+use crate::shaping::{line_direction, shape_span, FontFeatures, ShapedGlyph};
+use crate::spacing::{LineHeight, Spacing};
+use crate::stanza::split_evenly;
 
-pub struct Metrics<'a> {
-    pub font: Font<'a>,
-    pub scale: Scale,
+pub struct Metrics {
+    pub family: FontFamily,
+    pub scale: PxScale,
     pub font_size: f32,
     pub v_metrics: VMetrics,
     pub top_offset: f32,
     pub left_offset: f32,
+    /// The size of image being laid out.
+    pub dimensions: Dimension,
+    /// The size the type starts at before shrinking to fit.
+    pub max_font_size: f32,
+    pub features: FontFeatures,
+    pub spacing: Spacing,
+    pub alignment: Alignment,
+    pub hanging_punctuation: f32,
+    pub auto_optical_size: bool,
+    pub column_count: usize,
+    /// The space between columns, in ems.
+    pub column_gap: f32,
+    /// How many times the lines have been laid out while fitting them.
+    pub fit_iterations: usize,
+    /// The box covering the rendered text's ink.
+    pub ink_box: BoundingBox,
+    /// The box covering the lines' advances and the font's ascent and descent.
+    pub line_box: BoundingBox,
+    /// The lines on each page. There's only one page unless the poem had
+    /// to be split up to stay above the minimum font size.
+    pub pages: Vec<Vec<StyledLine>>,
 }
 
-impl<'a> Metrics<'a> {
-    pub fn new(font: Font<'a>, font_size: f32, top_offset: f32, left_offset: f32) -> Self {
-        let scale = Scale::uniform(font_size);
-        let v_metrics = font.v_metrics(scale);
+impl Metrics {
+    pub fn new(family: FontFamily, font_size: f32, top_offset: f32, left_offset: f32) -> Self {
+        let scale = PxScale::from(font_size);
+        let v_metrics = family.regular().v_metrics(scale);
         Metrics {
-            font,
+            family,
             scale,
             font_size,
             v_metrics,
             top_offset,
             left_offset,
+            dimensions: Dimension::new(0, 0),
+            max_font_size: font_size,
+            features: FontFeatures::default(),
+            spacing: Spacing::default(),
+            alignment: Alignment::default(),
+            hanging_punctuation: 0.0,
+            auto_optical_size: false,
+            column_count: 1,
+            column_gap: 0.0,
+            fit_iterations: 0,
+            ink_box: BoundingBox::empty(),
+            line_box: BoundingBox::empty(),
+            pages: Vec::new(),
         }
     }
 
-    pub fn compute_metrics(options: &PoetryWallOptions, poem: &Poem, font: Font<'a>) -> Self {
-        let mut metrics = Metrics::new(font, options.font_size, 0.0, 0.0);
-        let bounding_box = loop {
-            let glyphs = metrics.create_glyphs(poem.lines());
-            let bb = BoundingBox::compute_bounding_box(&glyphs);
-            if ((bb.bottom - bb.top) as u32) < options.dimensions.height {
-                break bb;
-            }
-            metrics.rescale_by(0.9);
+    pub fn compute_metrics(
+        options: &PoetryWallOptions,
+        dimensions: Dimension,
+        poem: &Poem,
+        family: FontFamily,
+    ) -> Self {
+        let dpi = options.resolution();
+        let (width, height) = (dimensions.width as f32, dimensions.height as f32);
+        let max_font_size = options.font_size.to_pixels(dpi, 0.0, height);
+        let mut metrics = Metrics::new(family, max_font_size, 0.0, 0.0);
+        metrics.dimensions = dimensions;
+        metrics.features = options.features.clone();
+        metrics.auto_optical_size =
+            !options.variations.contains(b"opsz") && metrics.family.has_axis(b"opsz");
+        metrics.rescale_to(max_font_size);
+        metrics.spacing = options.spacing.clone();
+        metrics.alignment = options.alignment;
+        metrics.hanging_punctuation = options.hanging_punctuation;
+        if let LineHeight::Absolute(pixels) = metrics.spacing.line_height {
+            // Keep the same proportion to the type as it shrinks to fit.
+            metrics.spacing.line_height = LineHeight::Multiple(pixels / metrics.em_size());
+        }
+        metrics.column_gap = options.column_gap;
+        let lines = poem.styled_lines();
+        metrics.pages = vec![lines.clone()];
+        let (ink_box, line_box) = match options.min_font_size {
+            Some(min_font_size) => metrics.paginate(&lines, min_font_size.to_pixels(dpi, 0.0, height), options),
+            None => metrics.fit_columns(options),
         };
-        let rendered_height = (bounding_box.bottom - bounding_box.top) as u32;
-        let rendered_width = (bounding_box.right - bounding_box.left) as u32;
-
-        metrics.top_offset = options
-            .top
-            .map(|v| v as f32)
-            .unwrap_or_else(|| 0.33 * (options.dimensions.height - rendered_height) as f32);
-        metrics.left_offset = options
-            .left
-            .map(|v| v as f32)
-            .unwrap_or_else(|| 0.25 * (options.dimensions.width - rendered_width) as f32);
+
+        // That was laid out from the origin, so the boxes are offsets from
+        // where the text starts. The visible ink is what's placed
+        // vertically, but the lines are placed horizontally by their
+        // advances, so punctuation hanging into the margin stays there.
+        let (line_left, line_width) = if line_box.is_empty() {
+            (0, 0)
+        } else {
+            (line_box.left, line_box.width())
+        };
+        let em = metrics.em_size();
+        metrics.top_offset = options.top.map(|top| top.to_pixels(dpi, em, height)).unwrap_or_else(|| {
+            let column_length = metrics
+                .pages
+                .iter()
+                .flat_map(|page| split_evenly(page, metrics.column_count))
+                .map(|column| column.len())
+                .max()
+                .unwrap_or(0);
+            let extent = metrics.vertical_extent(&ink_box, &line_box, column_length);
+            let top = options
+                .vertical_placement
+                .top_offset(height, &extent);
+            let top = match options.baseline_grid {
+                Some(grid) => snap_to_grid(top + metrics.v_metrics.ascent, grid) - metrics.v_metrics.ascent,
+                None => top,
+            };
+            // Don't let the placement push the ink off of the image.
+            top.min(height - extent.ink_bottom).max(-extent.ink_top)
+        });
+        metrics.left_offset = options.left.map(|left| left.to_pixels(dpi, em, width)).unwrap_or_else(|| {
+            0.25 * dimensions.width.saturating_sub(line_width) as f32 - line_left as f32
+        });
+
+        (metrics.ink_box, metrics.line_box) = metrics.page_boxes();
 
         metrics
     }
 
+    /// Splits the lines into more and more pages, at stanzas where it can,
+    /// until the type fits at `min_font_size`, or until another page doesn't
+    /// let it be any bigger. This returns the ink and line boxes the
+    /// pages fit with.
+    fn paginate(
+        &mut self,
+        lines: &[StyledLine],
+        min_font_size: f32,
+        options: &PoetryWallOptions,
+    ) -> (BoundingBox, BoundingBox) {
+        let mut boxes = self.fit_columns(options);
+        let mut count = 1;
+        while self.font_size < min_font_size {
+            count += 1;
+            let pages = split_evenly(lines, count);
+            if pages.len() < count {
+                break;
+            }
+            let previous = (std::mem::take(&mut self.pages), self.font_size);
+            self.pages = add_continued_markers(pages, options.continued_marker.as_deref());
+            let next_boxes = self.fit_columns(options);
+            if self.font_size <= previous.1 {
+                // The lines are too wide, so more pages won't help.
+                self.pages = previous.0;
+                return self.fit_columns(options);
+            }
+            boxes = next_boxes;
+        }
+        boxes
+    }
+
+    /// Works out the columns and shrinks the type until every page fits.
+    fn fit_columns(&mut self, options: &PoetryWallOptions) -> (BoundingBox, BoundingBox) {
+        self.column_count = match options.columns {
+            Columns::Count(count) => count,
+            Columns::Auto => self.best_column_count(),
+        };
+        self.rescale_to(self.max_font_size);
+        self.fit()
+    }
+
+    /// Shrinks the type until all of the pages, laid out from the origin, fit
+    /// in the image. This returns the ink and line boxes covering all of them.
+    fn fit(&mut self) -> (BoundingBox, BoundingBox) {
+        loop {
+            self.fit_iterations += 1;
+            let (ink_box, line_box) = self.page_boxes();
+            if ink_box.height() < self.dimensions.height && ink_box.width() < self.dimensions.width {
+                return (ink_box, line_box);
+            }
+            self.rescale_by(0.9);
+        }
+    }
+
+    /// The ink and line boxes covering all of the pages laid out where they
+    /// are now.
+    fn page_boxes(&self) -> (BoundingBox, BoundingBox) {
+        self.pages.iter().map(|page| self.layout_page(page)).fold(
+            (BoundingBox::empty(), BoundingBox::empty()),
+            |(ink_box, line_box), page| (ink_box.union(page.ink_box), line_box.union(page.line_box)),
+        )
+    }
+
+    /// The number of columns that fits the biggest type, preferring fewer
+    /// columns when it's a tie.
+    fn best_column_count(&mut self) -> usize {
+        let mut best = (1, 0.0);
+        for count in 1..=MAX_AUTO_COLUMNS {
+            if self.pages.iter().all(|page| split_evenly(page, count).len() < count) {
+                break;
+            }
+            self.column_count = count;
+            self.rescale_to(self.max_font_size);
+            self.fit();
+            if self.font_size > best.1 {
+                best = (count, self.font_size);
+            }
+        }
+        best.0
+    }
+
+    /// Lays out the pages and everything needed to render them.
+    pub fn layout_plan(&mut self, options: &PoetryWallOptions) -> LayoutPlan {
+        let pages = self.pages.clone();
+        let pages = pages
+            .iter()
+            .enumerate()
+            .map(|(i, lines)| {
+                let mut page = self.layout_page(lines);
+                if options.page_numbers && pages.len() > 1 {
+                    let label = format!("{}/{}", i + 1, pages.len());
+                    page.lines.push(self.layout_page_number(&label));
+                }
+                page
+            })
+            .collect();
+        LayoutPlan {
+            width: self.dimensions.width,
+            height: self.dimensions.height,
+            font: plan_font(&options.font_file),
+            variations: options.variations.to_string(),
+            auto_optical_size: self.auto_optical_size,
+            font_size: self.font_size,
+            top_offset: self.top_offset,
+            left_offset: self.left_offset,
+            ink_box: self.ink_box,
+            line_box: self.line_box,
+            pages,
+        }
+    }
+
+    /// Places a page number centered at the bottom of the image, at half the
+    /// size of the poem.
+    fn layout_page_number(&mut self, label: &str) -> LinePlan {
+        let saved = (self.font_size, self.top_offset, self.left_offset, self.column_count, self.alignment);
+        self.rescale_to(saved.0 / 2.0);
+        self.column_count = 1;
+        self.alignment = Alignment::Left;
+        self.top_offset = 0.0;
+        self.left_offset = 0.0;
+        let line = vec![Span::new(label, FontStyle::Regular)];
+        let width = self.layout_page(std::slice::from_ref(&line)).line_box.width();
+        self.left_offset = (self.dimensions.width as f32 - width as f32) / 2.0;
+        self.top_offset = self.dimensions.height as f32 - 1.5 * self.em_size() - self.v_metrics.ascent;
+        let mut page = self.layout_page(std::slice::from_ref(&line));
+
+        self.rescale_to(saved.0);
+        (_, self.top_offset, self.left_offset, self.column_count, self.alignment) = saved;
+        page.lines.remove(0)
+    }
+
     pub fn rescale_to(&mut self, font_size: f32) {
         self.font_size = font_size;
-        self.scale = Scale::uniform(font_size);
-        self.v_metrics = self.font.v_metrics(self.scale);
+        self.scale = PxScale::from(font_size);
+        if self.auto_optical_size {
+            self.family.set_optical_size(self.scale);
+        }
+        self.v_metrics = self.family.regular().v_metrics(self.scale);
     }
 
     pub fn rescale_by(&mut self, factor: f32) {
         self.rescale_to(self.font_size * factor);
     }
 
-    pub fn create_glyphs(&self, lines: &Vec<String>) -> GlyphVec {
-        let mut glyphs = Vec::new();
+    /// The vertical extent of text laid out from the origin. Without any ink,
+    /// the line box stands in for it.
+    fn vertical_extent(&self, ink_box: &BoundingBox, line_box: &BoundingBox, line_count: usize) -> VerticalExtent {
+        let ink = if ink_box.is_empty() { line_box } else { ink_box };
+        let (ink_top, ink_bottom) = if ink.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (ink.top as f32, ink.bottom as f32)
+        };
+        let first_baseline = self.v_metrics.ascent;
+        VerticalExtent {
+            ink_top,
+            ink_bottom,
+            cap_top: first_baseline - self.family.regular().cap_height(self.scale),
+            last_baseline: first_baseline + line_count.saturating_sub(1) as f32 * self.line_height(),
+        }
+    }
+
+    /// The size of an em in pixels at the current scale.
+    pub fn em_size(&self) -> f32 {
+        self.family.regular().em_size(self.scale)
+    }
+
+    /// The distance from one baseline to the next at the current scale.
+    pub fn line_height(&self) -> f32 {
+        match self.spacing.line_height {
+            LineHeight::Normal => {
+                self.v_metrics.ascent + self.v_metrics.descent.abs() + self.v_metrics.line_gap
+            }
+            LineHeight::Multiple(multiple) => multiple * self.em_size(),
+            LineHeight::Absolute(pixels) => pixels,
+        }
+    }
+
+    pub fn create_glyphs(&self, lines: &[StyledLine]) -> GlyphVec {
+        self.layout_lines(lines).0
+    }
+
+    /// Places the glyphs for the lines, and returns them with the line box.
+    /// That's the box the lines' advances and the font's ascent and descent
+    /// cover, leaving out any hanging punctuation.
+    pub fn layout_lines(&self, lines: &[StyledLine]) -> (GlyphVec, BoundingBox) {
+        let page = self.layout_page(lines);
+        let glyphs = page
+            .lines
+            .iter()
+            .flat_map(|line| line.positioned_glyphs(&self.family))
+            .collect();
+        (glyphs, page.line_box)
+    }
+
+    /// Lays a page's lines out in columns side by side, each as wide as its
+    /// longest line.
+    pub fn layout_page(&self, lines: &[StyledLine]) -> PagePlan {
+        let gap = self.column_gap * self.em_size();
+        let mut planned_lines = Vec::new();
+        let mut line_box = BoundingBox::empty();
+        let mut rules = Vec::new();
+        let mut left = self.left_offset;
+        for (i, column) in split_evenly(lines, self.column_count).into_iter().enumerate() {
+            if i > 0 {
+                rules.push(left - gap / 2.0);
+            }
+            let (column_box, block_width) = self.layout_column(column, left, &mut planned_lines);
+            line_box = line_box.union(column_box);
+            left += block_width + gap;
+        }
+        let ink_box = planned_lines
+            .iter()
+            .map(|line| BoundingBox::compute_bounding_box(&line.positioned_glyphs(&self.family)))
+            .fold(BoundingBox::empty(), BoundingBox::union);
+        PagePlan {
+            lines: planned_lines,
+            ink_box,
+            line_box,
+            column_rules: rules,
+        }
+    }
+
+    /// Places the glyphs for one column starting at `left`, and returns its
+    /// line box and width.
+    fn layout_column(&self, lines: &[StyledLine], left: f32, planned: &mut Vec<LinePlan>) -> (BoundingBox, f32) {
+        // Trailing spaces, like a Markdown hard break, don't take up room,
+        // and shouldn't stop the punctuation before them from hanging.
+        let trimmed_lines = lines.iter().map(|line| trim_line_end(line)).collect::<Vec<_>>();
+        let shaped_lines = trimmed_lines.iter().map(|line| self.shape_line(line)).collect::<Vec<_>>();
+        let measures = trimmed_lines
+            .iter()
+            .zip(&shaped_lines)
+            .map(|(line, shaped_line)| self.measure_line(line, shaped_line))
+            .collect::<Vec<_>>();
+        let block_width = measures.iter().map(|&(width, _)| width).fold(0.0, f32::max);
+
+        let mut line_box = BoundingBox::empty();
         let mut top = self.top_offset + self.v_metrics.ascent;
-        let line_height =
-            self.v_metrics.ascent + self.v_metrics.descent.abs() + self.v_metrics.line_gap;
-        let left = self.left_offset;
-        for text in lines {
-            let mut line_glyphs = self
-                .font
-                .layout(&text, self.scale, point(left, top))
-                .collect::<Vec<_>>();
+        let line_height = self.line_height();
+        for ((line, shaped_line), &(width, hang)) in lines.iter().zip(&shaped_lines).zip(&measures) {
+            let left = left + self.alignment.offset(width, block_width);
+            line_box = line_box.union(BoundingBox {
+                top: (top - self.v_metrics.ascent).floor() as i32,
+                left: left.floor() as i32,
+                bottom: (top - self.v_metrics.descent).ceil() as i32,
+                right: (left + width).ceil() as i32,
+            });
+            let mut glyphs = Vec::new();
+            let mut x = left - hang;
+            // Each span's glyphs are in visual order, but the spans themselves
+            // are in reading order.
+            let right_to_left = shaped_line.first().is_some_and(|shaped| shaped.direction == Direction::RightToLeft);
+            let mut shaped_spans: Vec<&ShapedSpan> = shaped_line.iter().collect();
+            if right_to_left {
+                shaped_spans.reverse();
+            }
+            for shaped_span in shaped_spans {
+                x = self.position_span(shaped_span, x, top, &mut glyphs);
+            }
+            planned.push(LinePlan {
+                text: line.iter().map(|span| span.text.as_str()).collect(),
+                size: self.font_size,
+                left,
+                width,
+                baseline: top,
+                glyphs,
+            });
             top += line_height;
-            glyphs.append(&mut line_glyphs);
         }
-        glyphs
+        (line_box, block_width)
+    }
+
+    /// Shapes each span of a line in its face, with the rest of the line
+    /// around it so letters join across styles.
+    fn shape_line(&self, line: &[Span]) -> Vec<ShapedSpan> {
+        let text = line.iter().map(|span| span.text.as_str()).collect::<String>();
+        let direction = line_direction(&text);
+        let mut start = 0;
+        line.iter()
+            .map(|span| {
+                let range = start..start + span.text.len();
+                start = range.end;
+                self.shape_span(span, &text, range, direction)
+            })
+            .collect()
+    }
+
+    /// Shapes a span in its face and works out how far the pen moves after
+    /// each glyph.
+    fn shape_span(&self, span: &Span, line: &str, range: Range<usize>, direction: Direction) -> ShapedSpan {
+        let (font, synthesis) = self.family.face(span.style);
+        let pixels_per_unit = font.pixels_per_unit(self.scale);
+        let embolden = synthesis.embolden_width(self.scale) as f32;
+        let em_size = font.em_size(self.scale);
+        let glyphs = shape_span(font, line, range, &self.features);
+        let advances = (0..glyphs.len())
+            .map(|i| {
+                glyphs[i].x_advance as f32 * pixels_per_unit
+                    + embolden
+                    + self.extra_spacing(&span.text, &glyphs, i) * em_size
+            })
+            .collect();
+        ShapedSpan {
+            style: span.style,
+            direction,
+            pixels_per_unit,
+            embolden,
+            glyphs,
+            advances,
+        }
+    }
+
+    /// The width of a line, leaving out the punctuation that hangs into the
+    /// margins, and how far it hangs into the left margin. Punctuation only
+    /// hangs into the right margin when the lines aren't aligned on the left,
+    /// and not at all in right-to-left lines.
+    fn measure_line(&self, line: &[Span], shaped_line: &[ShapedSpan]) -> (f32, f32) {
+        let advance = shaped_line.iter().flat_map(|shaped| &shaped.advances).sum::<f32>();
+        if shaped_line.first().is_some_and(|shaped| shaped.direction == Direction::RightToLeft) {
+            return (advance, 0.0);
+        }
+        let mut spans = line.iter().zip(shaped_line).filter(|(span, _)| !span.text.is_empty());
+
+        let hang_start = match spans.next() {
+            Some((span, shaped)) if span.text.starts_with(hangs_at_start) => shaped
+                .glyphs
+                .first()
+                .filter(|glyph| glyph.cluster == 0)
+                .map_or(0.0, |_| shaped.glyph_advance(0) * self.hanging_punctuation),
+            _ => 0.0,
+        };
+        let hang_end = match line.iter().zip(shaped_line).rfind(|(span, _)| !span.text.is_empty()) {
+            Some((span, shaped)) if self.alignment != Alignment::Left => {
+                let (last_char_index, last_char) = span.text.char_indices().next_back().unwrap();
+                let last = shaped.glyphs.len() - 1;
+                if hangs_at_end(last_char) && shaped.glyphs[last].cluster as usize == last_char_index {
+                    shaped.glyph_advance(last) * self.hanging_punctuation
+                } else {
+                    0.0
+                }
+            }
+            _ => 0.0,
+        };
+
+        (advance - hang_start - hang_end, hang_start)
+    }
+
+    /// Places a shaped span's glyphs with the pen starting at `left` on the
+    /// baseline `top`. This returns where the pen ends up.
+    fn position_span(&self, shaped: &ShapedSpan, left: f32, top: f32, glyphs: &mut Vec<GlyphPlan>) -> f32 {
+        let mut x = left;
+        let mut y = top;
+        for (glyph, advance) in shaped.glyphs.iter().zip(&shaped.advances) {
+            glyphs.push(GlyphPlan {
+                id: glyph.glyph_id,
+                style: shaped.style,
+                x: x + glyph.x_offset as f32 * shaped.pixels_per_unit,
+                y: y - glyph.y_offset as f32 * shaped.pixels_per_unit,
+            });
+            x += advance;
+            y -= glyph.y_advance as f32 * shaped.pixels_per_unit;
+        }
+        x
+    }
+
+    /// The letter and word spacing, in ems, that goes after the `i`th glyph.
+    /// It only goes after the last glyph of a cluster, so ligatures and
+    /// combining marks stay together.
+    fn extra_spacing(&self, text: &str, shaped_glyphs: &[ShapedGlyph], i: usize) -> f32 {
+        let cluster = shaped_glyphs[i].cluster;
+        if shaped_glyphs.get(i + 1).is_some_and(|next| next.cluster == cluster) {
+            return 0.0;
+        }
+        let is_space = text[cluster as usize..].starts_with(char::is_whitespace);
+        self.spacing.letter + if is_space { self.spacing.word } else { 0.0 }
+    }
+}
+
+/// The font as a plan records it: a font file by its full path, so the plan
+/// works from any directory, and a built-in font by its name.
+fn plan_font(font_file: &Path) -> PathBuf {
+    match builtin_name(font_file) {
+        Some(_) => font_file.to_path_buf(),
+        None => fs::canonicalize(font_file).unwrap_or_else(|_| font_file.to_path_buf()),
+    }
+}
+
+/// The line without the whitespace at its end, dropping any spans that are
+/// left empty.
+fn trim_line_end(line: &[Span]) -> StyledLine {
+    let mut line = line.to_vec();
+    while let Some(span) = line.last_mut() {
+        span.text.truncate(span.text.trim_end().len());
+        if !span.text.is_empty() {
+            break;
+        }
+        line.pop();
+    }
+    line
+}
+
+/// A span that's been shaped in its face, with how far the pen moves after
+/// each glyph, in pixels.
+struct ShapedSpan {
+    style: FontStyle,
+    /// The direction of the whole line.
+    direction: Direction,
+    pixels_per_unit: f32,
+    embolden: f32,
+    glyphs: Vec<ShapedGlyph>,
+    advances: Vec<f32>,
+}
+
+impl ShapedSpan {
+    /// A glyph's own advance, without any letter or word spacing.
+    fn glyph_advance(&self, i: usize) -> f32 {
+        self.glyphs[i].x_advance as f32 * self.pixels_per_unit + self.embolden
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use spectral::prelude::*;
+
+    use crate::alignment::Alignment;
+    use crate::dimension::Dimension;
+    use crate::font::{Font, FontFamily, FontStyle};
+    use crate::layout::LayoutPlan;
+    use crate::metrics::Metrics;
+    use crate::options::PoetryWallOptions;
+    use crate::poem::Poem;
+    use crate::spacing::{LineHeight, Spacing};
+    use crate::units::Length;
+
+    fn plan(poem: &str, options: PoetryWallOptions) -> LayoutPlan {
+        let font = Font::from_bytes(include_bytes!("../tests/fixtures/DejaVuSansMono.ttf").to_vec()).unwrap();
+        let poem = Poem::from_str(poem).unwrap();
+        let mut metrics = Metrics::compute_metrics(&options, Dimension::new(640, 240), &poem, FontFamily::new(font));
+        metrics.layout_plan(&options)
+    }
+
+    #[test]
+    fn shrinks_letter_spaced_lines_to_fit_the_width() {
+        let font_size = |letter_spacing| {
+            let options = PoetryWallOptions {
+                spacing: Spacing::new(letter_spacing, 0.0, LineHeight::default()),
+                ..PoetryWallOptions::default()
+            };
+            plan("a Fly buzz\n", options).font_size
+        };
+        // At the default 72 pixels, the line fits without spacing but not
+        // with a whole em between letters.
+        assert_that(&font_size(0.0)).is_equal_to(72.0);
+        assert_that(&font_size(1.0)).is_less_than(54.0);
+    }
+
+    #[test]
+    fn hangs_punctuation_before_a_hard_break() {
+        let line = |poem: &str| {
+            let options = PoetryWallOptions {
+                font_size: Length::px(24.0),
+                alignment: Alignment::Right,
+                hanging_punctuation: 1.0,
+                ..PoetryWallOptions::default()
+            };
+            let line = &plan(poem, options).pages[0].lines[0];
+            (line.left, line.width)
+        };
+        let (left, width) = line("The Stillness in the Room –  \n");
+        assert_that(&(left, width)).is_equal_to(line("The Stillness in the Room –\n"));
+        assert_that(&width).is_less_than(line("The Stillness in the Room x\n").1);
+    }
+
+    #[test]
+    fn lays_right_to_left_spans_out_from_the_right() {
+        let options = PoetryWallOptions { font_size: Length::px(24.0), ..PoetryWallOptions::default() };
+        let plan = plan("שלום *עולם*\n", options);
+        let glyphs = &plan.pages[0].lines[0].glyphs;
+        let xs = |style| glyphs.iter().filter(move |glyph| glyph.style == style).map(|glyph| glyph.x);
+        // The emphasized word comes second, so it's on the left.
+        let italic_right = xs(FontStyle::Italic).fold(f32::MIN, f32::max);
+        let regular_left = xs(FontStyle::Regular).fold(f32::MAX, f32::min);
+        assert_that(&italic_right).is_less_than(regular_left);
     }
 }
@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+use crate::dimension::Dimension;
+use crate::image_utils::ImageFormat;
+
+/// How images are named when a poem is rendered at several sizes.
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{stem}-{width}x{height}.{ext}";
+
+/// The file one size of a poem goes in, next to `output_file`. The template
+/// can use `{stem}` and `{ext}` from `output_file`, and the `{width}` and
+/// `{height}` of the image. Without an extension, `{ext}` is the format's.
+pub fn sized_file_name(
+    output_file: &Path,
+    template: &str,
+    dimensions: &Dimension,
+    format: Option<ImageFormat>,
+) -> PathBuf {
+    let stem = output_file.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = match output_file.extension() {
+        Some(extension) => extension.to_string_lossy(),
+        None => format.unwrap_or(ImageFormat::Png).extension().into(),
+    };
+    let name = template
+        .replace("{stem}", &stem)
+        .replace("{ext}", &extension)
+        .replace("{width}", &dimensions.width.to_string())
+        .replace("{height}", &dimensions.height.to_string());
+    output_file.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use spectral::prelude::*;
+
+    use crate::dimension::Dimension;
+    use crate::image_utils::ImageFormat;
+    use crate::naming::{sized_file_name, DEFAULT_OUTPUT_TEMPLATE};
+
+    #[test]
+    fn names_files_from_the_template() {
+        let dimensions = Dimension::new(1170, 2532);
+        assert_that(&sized_file_name(Path::new("out/wall.png"), DEFAULT_OUTPUT_TEMPLATE, &dimensions, None))
+            .is_equal_to(PathBuf::from("out/wall-1170x2532.png"));
+        assert_that(&sized_file_name(Path::new("wall.png"), "{stem}@{height}.png", &dimensions, None))
+            .is_equal_to(PathBuf::from("wall@2532.png"));
+    }
+
+    #[test]
+    fn names_files_without_an_extension() {
+        let dimensions = Dimension::new(1920, 1080);
+        assert_that(&sized_file_name(Path::new("wall"), DEFAULT_OUTPUT_TEMPLATE, &dimensions, None))
+            .is_equal_to(PathBuf::from("wall-1920x1080.png"));
+        let jpeg = Some(ImageFormat::Jpeg);
+        assert_that(&sized_file_name(Path::new("wall"), DEFAULT_OUTPUT_TEMPLATE, &dimensions, jpeg))
+            .is_equal_to(PathBuf::from("wall-1920x1080.jpg"));
+    }
+}
@@ -1,43 +1,218 @@
+use std::collections::BTreeSet;
+use std::fmt;
 use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, Utc};
+use palette::Srgb;
+
+use crate::alignment::Alignment;
+use crate::builtin::DEFAULT_FONT;
 use crate::color::Color;
+use crate::columns::Columns;
 use crate::dimension::Dimension;
+use crate::font::FontVariations;
+use crate::image_utils::ImageFormat;
+use crate::placement::VerticalPlacement;
+use crate::preview::Preview;
+use crate::shaping::FontFeatures;
+use crate::solar::{Palette, Solar};
+use crate::spacing::Spacing;
+use crate::typography::Language;
+use crate::units::{Length, DEFAULT_DPI};
+
+/// Where the poem is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Source {
+    /// The file named, or standard input for `-`.
+    pub fn from_arg(arg: &str) -> Self {
+        match arg {
+            "-" => Source::Stdin,
+            file => Source::File(PathBuf::from(file)),
+        }
+    }
+}
+
+/// Where the images are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sink {
+    Stdout,
+    File(PathBuf),
+}
+
+impl Sink {
+    /// The file named, or standard output for `-`.
+    pub fn from_arg(arg: &str) -> Self {
+        match arg {
+            "-" => Sink::Stdout,
+            file => Sink::File(PathBuf::from(file)),
+        }
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            Sink::Stdout => None,
+            Sink::File(file) => Some(file),
+        }
+    }
+}
 
-#[derive(Debug)]
+impl fmt::Display for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sink::Stdout => write!(f, "stdout"),
+            Sink::File(file) => write!(f, "{}", file.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct PoetryWallOptions {
-    pub poem_file: PathBuf,
+    /// The poem, unless it's rendered from a layout plan.
+    pub poem: Option<Source>,
     pub font_file: PathBuf,
-    pub font_size: f32,
+    /// The size the type starts at, which can be a percentage of each
+    /// image's height.
+    pub font_size: Length,
     pub color: Color,
     pub background: Color,
-    pub dimensions: Dimension,
-    pub top: Option<u32>,
-    pub left: Option<u32>,
-    pub output_file: PathBuf,
+    /// Day and night palettes to use instead of the color and background,
+    /// picked by the sun.
+    pub solar: Option<Solar>,
+    /// The time to pick the solar palette for, instead of now.
+    pub time: Option<DateTime<Utc>>,
+    /// Every size to render the poem at.
+    pub dimensions: Vec<Dimension>,
+    pub top: Option<Length>,
+    pub left: Option<Length>,
+    /// Where the images go, if they're wanted.
+    pub output: Option<Sink>,
+    /// How to name each image when there are several sizes.
+    pub output_template: Option<String>,
+    /// The format to write images in, instead of the one from the output's
+    /// extension.
+    pub format: Option<ImageFormat>,
+    /// Whether to show the images in the terminal, and how.
+    pub preview: Option<Preview>,
+    /// Whether to set the images as the desktop's wallpaper once they're
+    /// written.
+    pub apply: bool,
+    /// The command to set the wallpaper with, for desktops that aren't
+    /// recognized.
+    pub apply_command: Option<String>,
+    pub features: FontFeatures,
+    pub variations: FontVariations,
+    pub spacing: Spacing,
+    pub typography: bool,
+    pub language: Language,
+    pub typography_report: bool,
+    pub alignment: Alignment,
+    pub hanging_punctuation: f32,
+    pub vertical_placement: VerticalPlacement,
+    pub baseline_grid: Option<f32>,
+    pub columns: Columns,
+    pub column_gap: f32,
+    pub column_rule: Option<f32>,
+    pub min_font_size: Option<Length>,
+    pub continued_marker: Option<String>,
+    pub page_numbers: bool,
+    pub emit_layout: Option<PathBuf>,
+    pub layout_file: Option<PathBuf>,
+    pub debug_overlay: bool,
+    /// The resolution to size physical units at and to record in the PNG.
+    pub dpi: Option<f32>,
+    /// The long names of the options given on the command line, which win
+    /// over a poem's front matter.
+    pub command_line: BTreeSet<String>,
+}
+
+/// The same defaults as the command line, without a poem or an output file.
+impl Default for PoetryWallOptions {
+    fn default() -> Self {
+        PoetryWallOptions::new(
+            None,
+            DEFAULT_FONT,
+            Length::px(72.0),
+            Color::new(Srgb::new(255, 255, 255)),
+            Color::new(Srgb::new(0, 0, 0)),
+            vec![Dimension::new(1920, 1080)],
+            None,
+            None,
+            None,
+        )
+    }
 }
 
 impl PoetryWallOptions {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<P: AsRef<Path>>(
-        poem_file: P,
+        poem: Option<Source>,
         font_file: P,
-        font_size: f32,
+        font_size: Length,
         color: Color,
         background: Color,
-        dimensions: Dimension,
-        top: Option<u32>,
-        left: Option<u32>,
-        output_file: P,
+        dimensions: Vec<Dimension>,
+        top: Option<Length>,
+        left: Option<Length>,
+        output: Option<Sink>,
     ) -> Self {
         PoetryWallOptions {
-            poem_file: poem_file.as_ref().into(),
+            poem,
             font_file: font_file.as_ref().into(),
             font_size,
             color,
             background,
+            solar: None,
+            time: None,
             dimensions,
             left,
             top,
-            output_file: output_file.as_ref().into(),
+            output,
+            output_template: None,
+            format: None,
+            preview: None,
+            apply: false,
+            apply_command: None,
+            features: FontFeatures::default(),
+            variations: FontVariations::default(),
+            spacing: Spacing::default(),
+            typography: false,
+            language: Language::default(),
+            typography_report: false,
+            alignment: Alignment::default(),
+            hanging_punctuation: 0.0,
+            vertical_placement: VerticalPlacement::default(),
+            baseline_grid: None,
+            columns: Columns::default(),
+            column_gap: 2.0,
+            column_rule: None,
+            min_font_size: None,
+            continued_marker: None,
+            page_numbers: false,
+            emit_layout: None,
+            layout_file: None,
+            debug_overlay: false,
+            dpi: None,
+            command_line: BTreeSet::new(),
         }
     }
+
+    /// Replaces the color and background with the solar palette for the
+    /// time, if there is one.
+    pub fn resolve_palette(&mut self) {
+        if let Some(solar) = self.solar.take() {
+            let Palette { color, background } = solar.palette_at(self.time.unwrap_or_else(Utc::now));
+            self.color = color;
+            self.background = background;
+        }
+    }
+
+    /// The resolution to size physical units at.
+    pub fn resolution(&self) -> f32 {
+        self.dpi.unwrap_or(DEFAULT_DPI)
+    }
 }
@@ -0,0 +1,66 @@
+use ab_glyph::PxScale;
+use image::Rgba;
+
+use crate::bounding_box::BoundingBox;
+use crate::font::FontFamily;
+use crate::image_utils::{draw_box, draw_hline, draw_vline, Image};
+use crate::layout::{LayoutPlan, PagePlan};
+
+/// How far in from each edge the safe area starts, as a fraction of the
+/// image's size. Docks, menu bars and icons tend to cover what's outside it.
+const SAFE_AREA_INSET: f32 = 0.05;
+
+const SAFE_AREA_COLOR: Rgba<u8> = Rgba { data: [0, 255, 255, 255] };
+const MARGIN_COLOR: Rgba<u8> = Rgba { data: [255, 0, 255, 255] };
+const ASCENT_COLOR: Rgba<u8> = Rgba { data: [64, 255, 64, 255] };
+const BASELINE_COLOR: Rgba<u8> = Rgba { data: [255, 64, 64, 255] };
+const DESCENT_COLOR: Rgba<u8> = Rgba { data: [64, 128, 255, 255] };
+const LINE_BOX_COLOR: Rgba<u8> = Rgba { data: [255, 160, 0, 255] };
+const INK_BOX_COLOR: Rgba<u8> = Rgba { data: [255, 255, 0, 255] };
+
+/// The part of an image that's unlikely to be covered up.
+pub fn safe_area(width: u32, height: u32) -> BoundingBox {
+    let (dx, dy) = (width as f32 * SAFE_AREA_INSET, height as f32 * SAFE_AREA_INSET);
+    BoundingBox {
+        top: dy.round() as i32,
+        left: dx.round() as i32,
+        bottom: (height as f32 - dy).round() as i32,
+        right: (width as f32 - dx).round() as i32,
+    }
+}
+
+/// Draws the safe area, the margins, each line's ascent, baseline and
+/// descent, and the page's line and ink boxes over a rendered page.
+pub fn draw_overlay(image: &mut Image, plan: &LayoutPlan, page: &PagePlan, family: &FontFamily) {
+    let (width, height) = (plan.width as f32, plan.height as f32);
+    draw_box(image, &safe_area(plan.width, plan.height), SAFE_AREA_COLOR);
+    draw_hline(image, plan.top_offset, 0.0, width, MARGIN_COLOR);
+    draw_vline(image, plan.left_offset, 0.0, height, MARGIN_COLOR);
+
+    for line in &page.lines {
+        let v_metrics = family.regular().v_metrics(PxScale::from(line.size));
+        let right = line.left + line.width;
+        draw_hline(image, line.baseline - v_metrics.ascent, line.left, right, ASCENT_COLOR);
+        draw_hline(image, line.baseline - v_metrics.descent, line.left, right, DESCENT_COLOR);
+        draw_hline(image, line.baseline, line.left, right, BASELINE_COLOR);
+    }
+
+    draw_box(image, &page.line_box, LINE_BOX_COLOR);
+    draw_box(image, &page.ink_box, INK_BOX_COLOR);
+}
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use crate::overlay::safe_area;
+
+    #[test]
+    fn insets_the_safe_area() {
+        let area = safe_area(1920, 1080);
+        assert_that(&area.left).is_equal_to(96);
+        assert_that(&area.top).is_equal_to(54);
+        assert_that(&area.right).is_equal_to(1824);
+        assert_that(&area.bottom).is_equal_to(1026);
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+
+use crate::markup::{parse_emphasis, StyledLine};
+
+/// Adds the marker, after a blank line, to the end of every page but the
+/// last. The marker can use emphasis like the poem.
+pub fn add_continued_markers(pages: Vec<&[StyledLine]>, marker: Option<&str>) -> Vec<Vec<StyledLine>> {
+    let count = pages.len();
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(i, page)| {
+            let mut page = page.to_vec();
+            if let Some(marker) = marker.filter(|_| i + 1 < count) {
+                page.push(Vec::new());
+                page.push(parse_emphasis(marker));
+            }
+            page
+        })
+        .collect()
+}
+
+/// The file a page of a series goes in: `name.png` becomes `name-1.png`,
+/// `name-2.png` and so on.
+pub fn page_file_name(output_file: &Path, page: usize) -> PathBuf {
+    let stem = output_file.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output_file.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, page, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, page),
+    };
+    output_file.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use spectral::prelude::*;
+
+    use crate::markup::{parse_emphasis, StyledLine};
+    use crate::pagination::{add_continued_markers, page_file_name};
+
+    #[test]
+    fn numbers_page_files() {
+        assert_that(&page_file_name(Path::new("out/wall.png"), 2)).is_equal_to(PathBuf::from("out/wall-2.png"));
+        assert_that(&page_file_name(Path::new("wall"), 1)).is_equal_to(PathBuf::from("wall-1"));
+    }
+
+    #[test]
+    fn marks_all_but_the_last_page() {
+        let lines = [parse_emphasis("a"), parse_emphasis("b")];
+        let pages: Vec<&[StyledLine]> = vec![&lines[..1], &lines[1..]];
+        let pages = add_continued_markers(pages, Some("*continued*"));
+        assert_that(&pages[0].len()).is_equal_to(3);
+        assert_that(&pages[0][2][0].text).is_equal_to(String::from("continued"));
+        assert_that(&pages[1].len()).is_equal_to(1);
+    }
+}
@@ -0,0 +1,113 @@
+use std::str::FromStr;
+
+use crate::error::PoetryWallError;
+
+/// Where the optical center sits, as a fraction of the height. It's a little
+/// above the geometric center.
+const OPTICAL_CENTER: f32 = 0.46;
+
+/// The smaller part of a line divided in the golden ratio.
+const GOLDEN_SECTION: f32 = 0.382;
+
+/// How to place the poem vertically when `--top` isn't given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerticalPlacement {
+    /// A third of the free space above the ink and two thirds below.
+    #[default]
+    Third,
+    /// The free space above and below the ink in the golden ratio.
+    Golden,
+    /// The ink centered.
+    Center,
+    /// The block from the first line's cap height to the last line's
+    /// baseline centered a little above the middle, so tall ascenders and
+    /// descenders don't pull the poem around.
+    Optical,
+}
+
+/// The vertical extent of laid-out text, measured from the top of the line
+/// box. The first baseline and cap height are for the first line, and the
+/// last baseline is for the last line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VerticalExtent {
+    pub ink_top: f32,
+    pub ink_bottom: f32,
+    pub cap_top: f32,
+    pub last_baseline: f32,
+}
+
+impl VerticalPlacement {
+    /// The offset from the top of the image to the top of the line box.
+    pub fn top_offset(self, height: f32, extent: &VerticalExtent) -> f32 {
+        let free = (height - (extent.ink_bottom - extent.ink_top)).max(0.0);
+        match self {
+            VerticalPlacement::Third => 0.33 * free - extent.ink_top,
+            VerticalPlacement::Golden => GOLDEN_SECTION * free - extent.ink_top,
+            VerticalPlacement::Center => 0.5 * free - extent.ink_top,
+            VerticalPlacement::Optical => {
+                OPTICAL_CENTER * height - (extent.cap_top + extent.last_baseline) / 2.0
+            }
+        }
+    }
+}
+
+impl FromStr for VerticalPlacement {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "third" => Ok(VerticalPlacement::Third),
+            "golden" => Ok(VerticalPlacement::Golden),
+            "center" | "centre" => Ok(VerticalPlacement::Center),
+            "optical" => Ok(VerticalPlacement::Optical),
+            _ => Err(PoetryWallError::InvalidMissingOption(format!("vertical-placement: {}", s))),
+        }
+    }
+}
+
+/// Moves a baseline to the nearest line of a grid that starts at the top of
+/// the image.
+pub fn snap_to_grid(baseline: f32, grid: f32) -> f32 {
+    (baseline / grid).round() * grid
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use spectral::prelude::*;
+
+    use crate::placement::{snap_to_grid, VerticalExtent, VerticalPlacement};
+
+    const EXTENT: VerticalExtent = VerticalExtent {
+        ink_top: 10.0,
+        ink_bottom: 210.0,
+        cap_top: 12.0,
+        last_baseline: 200.0,
+    };
+
+    #[test]
+    fn reads_from_string() {
+        assert_that(&VerticalPlacement::from_str("golden")).is_ok().is_equal_to(VerticalPlacement::Golden);
+        assert_that(&VerticalPlacement::from_str("middle")).is_err();
+    }
+
+    #[test]
+    fn centers_the_ink() {
+        let top = VerticalPlacement::Center.top_offset(1000.0, &EXTENT);
+        assert_that(&(top + EXTENT.ink_top)).is_equal_to(400.0);
+    }
+
+    #[test]
+    fn centers_the_optical_block() {
+        let top = VerticalPlacement::Optical.top_offset(1000.0, &EXTENT);
+        let middle = top + (EXTENT.cap_top + EXTENT.last_baseline) / 2.0;
+        assert_that(&middle).is_close_to(460.0, 0.001);
+    }
+
+    #[test]
+    fn snaps_to_grid() {
+        assert_that(&snap_to_grid(130.0, 48.0)).is_equal_to(144.0);
+        assert_that(&snap_to_grid(100.0, 48.0)).is_equal_to(96.0);
+    }
+}
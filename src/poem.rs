@@ -1,45 +1,188 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
+use std::str::FromStr;
 
-use crate::error::Result;
+use crate::error::{PoetryWallError, Result};
+use crate::font::FontStyle;
+use crate::markup::{parse_emphasis, StyledLine};
+use crate::typography::{smarten, Language, TypographyReport};
+
+/// Settings for one poem, given as `key: value` lines between `---` lines at
+/// the top of the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontMatter(BTreeMap<String, String>);
+
+impl FrontMatter {
+    /// Splits the front matter off of the poem's lines. If the file doesn't
+    /// start with a `---` line that's closed later, or there's a line between
+    /// them that isn't a setting, there isn't any, and the rules are part of
+    /// the poem.
+    fn split(lines: Vec<String>) -> (FrontMatter, Vec<String>) {
+        let end = match lines.first() {
+            Some(first) if first.trim_end() == "---" => {
+                lines.iter().skip(1).position(|line| line.trim_end() == "---")
+            }
+            _ => None,
+        };
+        let end = match end {
+            Some(end) => end + 1,
+            None => return (FrontMatter::default(), lines),
+        };
+
+        let settings = lines[1..end]
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| setting(line))
+            .collect::<Option<BTreeMap<_, _>>>();
+        match settings {
+            Some(settings) => (FrontMatter(settings), lines.into_iter().skip(end + 1).collect()),
+            None => (FrontMatter::default(), lines),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Whether the `typography` setting turns smart punctuation on or off.
+    pub fn typography(&self) -> Result<Option<bool>> {
+        self.get("typography")
+            .map(|value| match value.to_lowercase().as_str() {
+                "on" | "true" | "yes" => Ok(true),
+                "off" | "false" | "no" => Ok(false),
+                _ => Err(PoetryWallError::FrontMatterError(format!("typography: {}", value))),
+            })
+            .transpose()
+    }
+
+    pub fn language(&self) -> Result<Option<Language>> {
+        self.get("lang").map(str::parse).transpose()
+    }
+}
+
+/// A `key: value` line, where the key is a single word.
+fn setting(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim();
+    let is_word = !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    is_word.then(|| (key.to_lowercase(), String::from(value.trim())))
+}
 
 #[derive(Debug)]
-pub struct Poem(Vec<String>);
+pub struct Poem {
+    lines: Vec<String>,
+    front_matter: FrontMatter,
+    first_line: usize,
+}
 
 impl Poem {
     pub fn new(lines: Vec<String>) -> Self {
-        Poem(lines)
+        Poem {
+            lines,
+            front_matter: FrontMatter::default(),
+            first_line: 1,
+        }
     }
 
     pub fn from_file<P: AsRef<Path>>(filename: P) -> Result<Self> {
         let mut f = File::open(filename)?;
         let mut buffer = String::new();
         f.read_to_string(&mut buffer)?;
-        Ok(Poem::new(buffer.lines().map(|line| line.into()).collect()))
+        buffer.parse()
     }
 
     pub fn lines(&self) -> &Vec<String> {
-        &self.0
+        &self.lines
+    }
+
+    pub fn front_matter(&self) -> &FrontMatter {
+        &self.front_matter
+    }
+
+    /// Runs the typographic pass over the poem's lines and reports what it
+    /// changed.
+    pub fn smarten(&mut self, language: Language) -> TypographyReport {
+        let (lines, report) = smarten(&self.lines, language, self.first_line);
+        self.lines = lines;
+        report
+    }
+
+    /// The lines with their Markdown emphasis parsed into styled spans.
+    pub fn styled_lines(&self) -> Vec<StyledLine> {
+        self.lines.iter().map(|line| parse_emphasis(line)).collect()
+    }
+
+    pub fn has_emphasis(&self) -> bool {
+        self.styled_lines()
+            .iter()
+            .flatten()
+            .any(|span| span.style != FontStyle::Regular)
+    }
+}
+
+/// Reads a poem from its markdown, with any front matter.
+impl FromStr for Poem {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let all_lines = s.lines().map(|line| line.into()).collect::<Vec<String>>();
+        let line_count = all_lines.len();
+        let (front_matter, lines) = FrontMatter::split(all_lines);
+        let first_line = line_count - lines.len() + 1;
+        Ok(Poem {
+            lines,
+            front_matter,
+            first_line,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
     use spectral::prelude::*;
 
     use crate::error::PoetryWallError;
-    use crate::poem::Poem;
+    use crate::poem::{FrontMatter, Poem};
+    use crate::typography::Language;
 
     #[test]
     fn reads_from_file() {
         let poem = Poem::from_file("./tests/fixtures/fly-buzz.md");
         assert_that(&poem)
             .is_ok()
-            .map(|p| &p.0)
+            .map(|p| p.lines())
             .has_length(21);
     }
 
+    #[test]
+    fn reads_front_matter() {
+        let lines = vec!["---", "typography: on", "lang: fr", "---", "Je suis"];
+        let (front_matter, lines) =
+            FrontMatter::split(lines.into_iter().map(String::from).collect());
+        assert_that(&front_matter.typography()).is_ok().is_equal_to(Some(true));
+        assert_that(&front_matter.language()).is_ok().is_equal_to(Some(Language::French));
+        assert_that(&lines).is_equal_to(vec![String::from("Je suis")]);
+    }
+
+    #[test]
+    fn keeps_unclosed_front_matter() {
+        let lines = vec![String::from("---"), String::from("a: b")];
+        let (front_matter, lines) = FrontMatter::split(lines);
+        assert_that(&front_matter).is_equal_to(FrontMatter::default());
+        assert_that(&lines).has_length(2);
+    }
+
+    #[test]
+    fn keeps_rules_that_are_part_of_the_poem() {
+        let poem = Poem::from_str("---\nHope is the thing with feathers\nThat perches: in the soul\n---\n").unwrap();
+        assert_that(&poem.front_matter).is_equal_to(FrontMatter::default());
+        assert_that(poem.lines()).has_length(4);
+    }
+
     #[test]
     fn returns_error_missing_file() {
         let poem = Poem::from_file("./does/not/exist");
@@ -0,0 +1,266 @@
+use std::env;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use image::{imageops, FilterType};
+
+use crate::error::{PoetryWallError, Result};
+use crate::image_utils::{encode_image, Image, ImageFormat};
+
+/// The size of a terminal cell, in pixels, for sixels. Terminals don't
+/// reliably say, and this is close for most fonts.
+const CELL_WIDTH: u32 = 10;
+const CELL_HEIGHT: u32 = 20;
+
+/// How much base64 kitty takes in each escape sequence.
+const KITTY_CHUNK: usize = 4096;
+
+/// How to draw an image in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preview {
+    /// Whichever of the others the terminal seems to support.
+    Auto,
+    /// The kitty graphics protocol, which kitty, WezTerm and Ghostty support.
+    Kitty,
+    Sixel,
+    /// Truecolor half blocks, which work nearly everywhere.
+    Blocks,
+}
+
+impl Preview {
+    /// Picks a protocol from the terminal's environment variables. Only
+    /// `TERM` makes it over SSH, so this errs toward blocks.
+    pub fn detect<F: Fn(&str) -> Option<String>>(var: F) -> Self {
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        let kitty = ["xterm-kitty", "xterm-ghostty"].contains(&term.as_str())
+            || ["WezTerm", "ghostty"].contains(&program.as_str())
+            || var("KITTY_WINDOW_ID").is_some();
+        let sixel = ["foot", "mlterm"].iter().any(|name| term.starts_with(name))
+            || term.contains("sixel")
+            || ["iTerm.app", "mintty"].contains(&program.as_str());
+        if kitty {
+            Preview::Kitty
+        } else if sixel {
+            Preview::Sixel
+        } else {
+            Preview::Blocks
+        }
+    }
+}
+
+impl FromStr for Preview {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Preview::Auto),
+            "kitty" => Ok(Preview::Kitty),
+            "sixel" => Ok(Preview::Sixel),
+            "blocks" => Ok(Preview::Blocks),
+            _ => Err(PoetryWallError::InvalidMissingOption(format!("preview-protocol: {}", s))),
+        }
+    }
+}
+
+/// Shows the image on standard output, scaled to fit the terminal.
+pub fn show_preview(image: &Image, preview: Preview) -> Result<()> {
+    let (columns, rows) = terminal_cells();
+    let preview = match preview {
+        Preview::Auto => Preview::detect(|name| env::var(name).ok()),
+        preview => preview,
+    };
+    let text = preview_text(image, preview, columns, rows)?;
+    let mut stdout = io::stdout();
+    stdout.write_all(text.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The escape sequences that draw the image in `columns` by `rows` cells,
+/// leaving a row for the prompt.
+pub fn preview_text(image: &Image, preview: Preview, columns: u32, rows: u32) -> Result<String> {
+    // Each cell is two half blocks tall, which makes them about square.
+    let (width, height) = fit(image.dimensions(), columns, rows.saturating_sub(1) * 2);
+    let (columns, rows) = (width, height.div_ceil(2));
+    match preview {
+        Preview::Kitty => kitty(image, columns, rows),
+        Preview::Sixel => {
+            let image = imageops::resize(image, columns * CELL_WIDTH, rows * CELL_HEIGHT, FilterType::Triangle);
+            Ok(sixel(&image))
+        }
+        Preview::Blocks | Preview::Auto => {
+            let image = imageops::resize(image, width, height, FilterType::Triangle);
+            Ok(half_blocks(&image))
+        }
+    }
+}
+
+/// The terminal's size in cells, from standard output or else `COLUMNS` and
+/// `LINES`.
+fn terminal_cells() -> (u32, u32) {
+    if let Some((terminal_size::Width(columns), terminal_size::Height(rows))) = terminal_size::terminal_size() {
+        return (columns as u32, rows as u32);
+    }
+    let var = |name, default| env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default);
+    (var("COLUMNS", 80), var("LINES", 24))
+}
+
+/// The largest size with the image's proportions that fits in the box.
+fn fit((width, height): (u32, u32), max_width: u32, max_height: u32) -> (u32, u32) {
+    let scale = (max_width as f32 / width as f32).min(max_height as f32 / height as f32);
+    let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+/// The image as a PNG, sent in chunks and drawn across the cells.
+fn kitty(image: &Image, columns: u32, rows: u32) -> Result<String> {
+    let data = base64(&encode_image(image, ImageFormat::Png, None)?);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut text = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        let chunk = String::from_utf8_lossy(chunk);
+        if i == 0 {
+            let _ = write!(text, "\x1b_Ga=T,f=100,c={},r={},m={};{}\x1b\\", columns, rows, more, chunk);
+        } else {
+            let _ = write!(text, "\x1b_Gm={};{}\x1b\\", more, chunk);
+        }
+    }
+    text.push('\n');
+    Ok(text)
+}
+
+/// The image in sixels, with its colors cut down to a 6×6×6 cube.
+fn sixel(image: &Image) -> String {
+    let (width, height) = image.dimensions();
+    let register = |x, y| {
+        let pixel = image.get_pixel(x, y).data;
+        let level = |value: u8| (value as u32 * 5 + 127) / 255;
+        (level(pixel[0]) * 36 + level(pixel[1]) * 6 + level(pixel[2])) as usize
+    };
+
+    let mut text = format!("\x1bPq\"1;1;{};{}", width, height);
+    for i in 0..216 {
+        let level = |value: usize| value * 100 / 5;
+        let _ = write!(text, "#{};2;{};{};{}", i, level(i / 36), level(i / 6 % 6), level(i % 6));
+    }
+    for band in (0..height).step_by(6) {
+        let rows = band..(band + 6).min(height);
+        let mut used = [false; 216];
+        for y in rows.clone() {
+            for x in 0..width {
+                used[register(x, y)] = true;
+            }
+        }
+        for color in (0..216).filter(|&color| used[color]) {
+            let _ = write!(text, "#{}", color);
+            let sixels: Vec<u8> = (0..width)
+                .map(|x| {
+                    let bits = rows.clone().filter(|&y| register(x, y) == color).fold(0, |bits, y| bits | 1 << (y - band));
+                    63 + bits
+                })
+                .collect();
+            let mut x = 0;
+            while x < sixels.len() {
+                let run = sixels[x..].iter().take_while(|&&sixel| sixel == sixels[x]).count();
+                if run > 3 {
+                    let _ = write!(text, "!{}", run);
+                } else {
+                    text.extend(std::iter::repeat_n(sixels[x] as char, run - 1));
+                }
+                text.push(sixels[x] as char);
+                x += run;
+            }
+            text.push('$');
+        }
+        text.push('-');
+    }
+    text.push_str("\x1b\\\n");
+    text
+}
+
+/// Two pixels to a cell: the upper one in the foreground of `▀`, and the
+/// lower one in the background.
+fn half_blocks(image: &Image) -> String {
+    let (width, height) = image.dimensions();
+    let mut text = String::new();
+    for y in (0..height).step_by(2) {
+        let mut colors = None;
+        for x in 0..width {
+            let upper = image.get_pixel(x, y).data;
+            let lower = if y + 1 < height { image.get_pixel(x, y + 1).data } else { upper };
+            if colors != Some((upper, lower)) {
+                let _ = write!(
+                    text,
+                    "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                    upper[0], upper[1], upper[2], lower[0], lower[1], lower[2]
+                );
+                colors = Some((upper, lower));
+            }
+            text.push('▀');
+        }
+        text.push_str("\x1b[0m\n");
+    }
+    text
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use spectral::prelude::*;
+
+    use crate::image_utils::create_image;
+    use crate::preview::{base64, fit, preview_text, Preview};
+
+    #[test]
+    fn detects_the_terminal() {
+        let detect = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> =
+                vars.iter().map(|&(name, value)| (String::from(name), String::from(value))).collect();
+            Preview::detect(|name| vars.get(name).cloned())
+        };
+        assert_that(&detect(&[("TERM", "xterm-kitty")])).is_equal_to(Preview::Kitty);
+        assert_that(&detect(&[("TERM", "foot")])).is_equal_to(Preview::Sixel);
+        assert_that(&detect(&[("TERM", "xterm-256color")])).is_equal_to(Preview::Blocks);
+        assert_that(&detect(&[])).is_equal_to(Preview::Blocks);
+    }
+
+    #[test]
+    fn fits_the_terminal() {
+        assert_that(&fit((1920, 1080), 80, 46)).is_equal_to((80, 45));
+        assert_that(&fit((1170, 2532), 80, 46)).is_equal_to((21, 46));
+        assert_that(&base64(b"poem")).is_equal_to(String::from("cG9lbQ=="));
+    }
+
+    #[test]
+    fn draws_each_protocol() {
+        let image = create_image(64, 32, 255, 128, 0);
+        let blocks = preview_text(&image, Preview::Blocks, 40, 21).unwrap();
+        assert_that(&blocks.lines().count()).is_equal_to(10);
+        assert_that(&blocks.starts_with("\x1b[38;2;255;128;0;48;2;255;128;0m▀")).is_true();
+        let kitty = preview_text(&image, Preview::Kitty, 40, 21).unwrap();
+        assert_that(&kitty.starts_with("\x1b_Ga=T,f=100,c=40,r=10,m=0;iVBOR")).is_true();
+        let sixel = preview_text(&image, Preview::Sixel, 40, 21).unwrap();
+        assert_that(&sixel.starts_with("\x1bPq\"1;1;400;200")).is_true();
+        assert_that(&sixel.ends_with("\x1b\\\n")).is_true();
+    }
+}
@@ -0,0 +1,317 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::batch::find_poems;
+use crate::create_poetry_wall;
+use crate::error::{PoetryWallError, Result};
+use crate::options::{PoetryWallOptions, Sink, Source};
+
+/// When to show the next poem: `hourly`, `daily` at midnight, or a cron
+/// expression like `0 7 * * *` for every morning at seven, in local time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Cron matches either the day of the month or the day of the week, when
+    /// both are given.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// The first time after `time` that this fires, if it ever does.
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = time.date().and_hms_opt(time.hour(), time.minute(), 0)? + chrono::Duration::minutes(1);
+        let end = start + chrono::Duration::days(5 * 366);
+        let mut time = start;
+        while time < end {
+            let date = time.date();
+            let next_day = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+            if !has(self.months, date.month()) {
+                let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.is_day(date) {
+                time = next_day;
+            } else if !has(self.hours, time.hour()) {
+                time = date.and_hms_opt(time.hour(), 0, 0)? + chrono::Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += chrono::Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn is_day(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let cron = match s.trim() {
+            "hourly" => "0 * * * *",
+            "daily" => "0 0 * * *",
+            cron => cron,
+        };
+        let error = || PoetryWallError::InvalidMissingOption(format!("schedule: {}", s));
+        let fields: Vec<&str> = cron.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(error());
+        }
+        let field = |i: usize, min, max| parse_cron_field(fields[i], min, max).ok_or_else(error);
+        // Sunday is both 0 and 7.
+        let weekdays = field(4, 0, 7)?;
+        Ok(Schedule {
+            minutes: field(0, 0, 59)?,
+            hours: field(1, 0, 23)?,
+            days: field(2, 1, 31)?,
+            months: field(3, 1, 12)?,
+            weekdays: (weekdays | weekdays >> 7) & 0x7F,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & 1 << value != 0
+}
+
+/// Reads a comma-separated list of `*`, numbers and ranges, each with an
+/// optional `/step`, into a bit set.
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse().ok().filter(|&step| step > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+            None if step > 1 => (range.parse().ok()?, max),
+            None => (range.parse().ok()?, range.parse().ok()?),
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step) {
+            set |= 1 << value;
+        }
+    }
+    Some(set)
+}
+
+/// The poems that have been shown, so they aren't again until the whole
+/// library has been.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    /// What the choices are made from, so the same library and history
+    /// always give the same poem.
+    pub seed: u64,
+    /// How many poems have been shown in all.
+    pub rotations: u64,
+    /// The poems shown since the library was last gone through.
+    pub shown: Vec<PathBuf>,
+}
+
+impl History {
+    pub fn new(seed: u64) -> Self {
+        History { seed, ..History::default() }
+    }
+
+    /// Reads the history, or starts one with the seed if there isn't one.
+    pub fn load(path: &Path, seed: u64) -> Result<Self> {
+        if !path.exists() {
+            return Ok(History::new(seed));
+        }
+        let error = |err: &dyn std::fmt::Display| PoetryWallError::HistoryError(format!("{}: {}", path.display(), err));
+        let json = fs::read_to_string(path).map_err(|err| error(&err))?;
+        serde_json::from_str(&json).map_err(|err| error(&err))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let error = |err: &dyn std::fmt::Display| PoetryWallError::HistoryError(format!("{}: {}", path.display(), err));
+        if let Some(directory) = path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+            fs::create_dir_all(directory).map_err(|err| error(&err))?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|err| error(&err))?;
+        fs::write(path, json).map_err(|err| error(&err))
+    }
+
+    /// Picks one of the poems that haven't been shown yet. Once they all
+    /// have, it starts over with any but the last one.
+    pub fn choose<'a>(&self, library: &'a [PathBuf]) -> Option<&'a PathBuf> {
+        let mut unseen: Vec<&PathBuf> = library.iter().filter(|poem| !self.shown.contains(poem)).collect();
+        if unseen.is_empty() {
+            unseen = library.iter().filter(|&poem| Some(poem) != self.shown.last()).collect();
+        }
+        if unseen.is_empty() {
+            return library.first();
+        }
+        let index = split_mix(self.seed ^ self.rotations) % unseen.len() as u64;
+        Some(unseen[index as usize])
+    }
+
+    /// Records that a poem was shown, starting over if that's the whole
+    /// library.
+    pub fn record(&mut self, poem: &Path, library: &[PathBuf]) {
+        if library.iter().all(|poem| self.shown.contains(poem)) {
+            self.shown.clear();
+        }
+        self.shown.push(poem.to_path_buf());
+        self.rotations += 1;
+    }
+}
+
+/// A quick, well-mixed hash of a number, so that nearby ones give unrelated
+/// choices.
+fn split_mix(n: u64) -> u64 {
+    let mut z = n.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Where the history is kept unless it's given.
+pub fn default_history_file() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("poetry-wall").join("history.json"))
+}
+
+/// A seed for a new history, when one isn't given.
+fn clock_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or_default()
+}
+
+/// Renders the next poem from the library, and records it in the history.
+/// The library is read again each time, so new poems join the rotation. A
+/// seed replaces the history's; without one, a new history gets one from the
+/// clock.
+pub fn rotate(library: &Path, history_file: &Path, seed: Option<u64>, options: &PoetryWallOptions) -> Result<PathBuf> {
+    let poems = find_poems(&[library.to_string_lossy()])?;
+    let mut history = History::load(history_file, seed.unwrap_or_else(clock_seed))?;
+    if let Some(seed) = seed {
+        history.seed = seed;
+    }
+    let poem = history
+        .choose(&poems)
+        .cloned()
+        .ok_or_else(|| PoetryWallError::InvalidMissingOption(format!("library: no poems in {}", library.display())))?;
+
+    let mut options = options.clone();
+    options.poem = Some(Source::File(poem.clone()));
+    let result = create_poetry_wall(&options);
+    // A poem that fails is passed over, so it doesn't come up every time.
+    history.record(&poem, &poems);
+    history.save(history_file)?;
+    result.map(|()| poem)
+}
+
+/// Shows a poem now, and then another each time the schedule comes around,
+/// until it's stopped. Errors are printed, and it carries on.
+pub fn run_daemon(
+    library: &Path,
+    schedule: &Schedule,
+    history_file: &Path,
+    seed: Option<u64>,
+    options: &PoetryWallOptions,
+) -> Result<()> {
+    if !matches!(options.output, Some(Sink::File(_))) {
+        return Err(PoetryWallError::InvalidMissingOption(String::from(
+            "output: the daemon has to write to a file",
+        )));
+    }
+    loop {
+        let now = Local::now().naive_local();
+        match rotate(library, history_file, seed, options) {
+            Ok(poem) => eprintln!("{}: {}", now.format("%Y-%m-%d %H:%M"), poem.display()),
+            Err(err) => eprintln!("{}: Error: {}", now.format("%Y-%m-%d %H:%M"), err),
+        }
+        let next = schedule
+            .next_after(now)
+            .ok_or_else(|| PoetryWallError::InvalidMissingOption(String::from("schedule: it never comes around")))?;
+        // Sleep a minute at a time, so a suspended laptop or a clock change
+        // doesn't throw it off.
+        while Local::now().naive_local() < next {
+            thread::sleep(Duration::from_secs(60).min((next - Local::now().naive_local()).to_std().unwrap_or_default()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::{NaiveDate, NaiveDateTime};
+    use spectral::prelude::*;
+
+    use crate::error::PoetryWallError;
+    use crate::rotation::{History, Schedule};
+
+    fn time(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // The first of March, 2024, was a Friday.
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn finds_the_next_time() {
+        let now = time(1, 18, 45);
+        assert_that(&"hourly".parse::<Schedule>().unwrap().next_after(now)).is_equal_to(Some(time(1, 19, 0)));
+        assert_that(&"daily".parse::<Schedule>().unwrap().next_after(now)).is_equal_to(Some(time(2, 0, 0)));
+        let weekdays: Schedule = "30 7 * * 1-5".parse().unwrap();
+        assert_that(&weekdays.next_after(now)).is_equal_to(Some(time(4, 7, 30)));
+        let quarter_hours: Schedule = "*/15 * * * *".parse().unwrap();
+        assert_that(&quarter_hours.next_after(time(1, 18, 30))).is_equal_to(Some(time(1, 18, 45)));
+        assert_that(&"0 7 * *".parse::<Schedule>()).is_err();
+        assert_that(&"61 * * * *".parse::<Schedule>()).is_err();
+    }
+
+    #[test]
+    fn reports_a_broken_history() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let file = dir.path().join("history.json");
+        std::fs::write(&file, "{").unwrap();
+        let err = History::load(&file, 42).unwrap_err();
+        assert_that(&matches!(err, PoetryWallError::HistoryError(_))).is_true();
+    }
+
+    #[test]
+    fn shows_every_poem_before_repeating() {
+        let library: Vec<PathBuf> = ["a.md", "b.md", "c.md"].iter().map(PathBuf::from).collect();
+        let rotate = |history: &mut History| {
+            let poem = history.choose(&library).unwrap().clone();
+            history.record(&poem, &library);
+            poem
+        };
+
+        let mut history = History::new(42);
+        let mut first: Vec<PathBuf> = (0..3).map(|_| rotate(&mut history)).collect();
+        let last = first.last().cloned();
+        assert_that(&rotate(&mut history)).is_not_equal_to(last.unwrap());
+        first.sort();
+        assert_that(&first).is_equal_to(library.clone());
+
+        let mut again = History::new(42);
+        let mut same = History::new(42);
+        assert_that(&(0..6).map(|_| rotate(&mut again)).collect::<Vec<_>>())
+            .is_equal_to((0..6).map(|_| rotate(&mut same)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,181 @@
+use std::ops::Range;
+use std::str::FromStr;
+
+use rustybuzz::{script, Direction, Feature, Script, UnicodeBuffer};
+
+use crate::error::PoetryWallError;
+use crate::font::Font;
+
+/// The OpenType features to turn on or off while shaping. Anything that isn't
+/// mentioned keeps the shaper's default, so kerning and standard ligatures are
+/// on unless they're explicitly turned off.
+#[derive(Debug, Clone, Default)]
+pub struct FontFeatures(Vec<Feature>);
+
+impl FontFeatures {
+    pub fn new(features: Vec<Feature>) -> Self {
+        FontFeatures(features)
+    }
+
+    pub fn features(&self) -> &[Feature] {
+        &self.0
+    }
+}
+
+impl FromStr for FontFeatures {
+    type Err = PoetryWallError;
+
+    /// Reads a comma-separated list of features, such as `liga,-kern,onum`.
+    /// A leading `-` turns a feature off, and `tag=N` picks an alternate.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|feature| !feature.is_empty())
+            .map(|feature| {
+                Feature::from_str(feature)
+                    .map_err(|_| PoetryWallError::FeatureReadError(String::from(feature)))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(FontFeatures)
+    }
+}
+
+/// A glyph coming out of the shaper. All of the measurements are in font
+/// units, and the y-axis points up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedGlyph {
+    pub glyph_id: u32,
+    pub cluster: u32,
+    pub x_advance: i32,
+    pub y_advance: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+/// Shapes one line of text. The script and direction are guessed from the
+/// text, and the glyphs come back in visual order, left to right.
+pub fn shape_line(font: &Font, text: &str, features: &FontFeatures) -> Vec<ShapedGlyph> {
+    shape_span(font, text, 0..text.len(), features)
+}
+
+/// Shapes the part of a line in `range`, such as one emphasized span, with
+/// the rest of the line as context, so letters still join across it. Text
+/// with no script of its own, like spaces and punctuation, takes the line's
+/// script and direction. The clusters are counted from the start of the span.
+pub fn shape_span(font: &Font, line: &str, range: Range<usize>, features: &FontFeatures) -> Vec<ShapedGlyph> {
+    let (mut script, mut direction) = segment_properties(&line[range.clone()]);
+    if script == script::UNKNOWN {
+        (script, direction) = segment_properties(line);
+    }
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(&line[range.clone()]);
+    buffer.set_pre_context(&line[..range.start]);
+    buffer.set_post_context(&line[range.end..]);
+    if script != script::UNKNOWN {
+        buffer.set_script(script);
+    }
+    buffer.set_direction(direction);
+
+    let shaped = rustybuzz::shape(&font.face(), features.features(), buffer);
+    shaped
+        .glyph_infos()
+        .iter()
+        .zip(shaped.glyph_positions())
+        .map(|(info, position)| ShapedGlyph {
+            glyph_id: info.glyph_id,
+            cluster: info.cluster,
+            x_advance: position.x_advance,
+            y_advance: position.y_advance,
+            x_offset: position.x_offset,
+            y_offset: position.y_offset,
+        })
+        .collect()
+}
+
+/// The direction a line runs in, from the first letter with a script.
+pub fn line_direction(text: &str) -> Direction {
+    segment_properties(text).1
+}
+
+fn segment_properties(text: &str) -> (Script, Direction) {
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+    (buffer.script(), buffer.direction())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use spectral::prelude::*;
+
+    use crate::font::load_font;
+    use rustybuzz::Direction;
+
+    use crate::shaping::{line_direction, shape_line, shape_span, FontFeatures};
+
+    #[test]
+    fn reads_features_from_string() {
+        let features = FontFeatures::from_str("liga, -kern,onum");
+        assert_that(&features)
+            .is_ok()
+            .map(|f| &f.0)
+            .has_length(3);
+    }
+
+    #[test]
+    fn turns_features_off() {
+        let features = FontFeatures::from_str("-kern").unwrap();
+        assert_that(&features.0[0].value).is_equal_to(0);
+    }
+
+    #[test]
+    fn fails_on_invalid_feature() {
+        let features = FontFeatures::from_str("liga,=kern");
+        assert_that(&features).is_err();
+    }
+
+    #[test]
+    fn shapes_left_to_right_text() {
+        let font = load_font(&"./tests/fixtures/DejaVuSansMono.ttf").unwrap();
+        let glyphs = shape_line(&font, "fly", &FontFeatures::default());
+        let clusters = glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>();
+        assert_that(&clusters).is_equal_to(vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn shapes_right_to_left_text_in_visual_order() {
+        let font = load_font(&"./tests/fixtures/DejaVuSansMono.ttf").unwrap();
+        let glyphs = shape_line(&font, "שלום", &FontFeatures::default());
+        let clusters = glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>();
+        assert_that(&clusters).is_equal_to(vec![6, 4, 2, 0]);
+    }
+
+    #[test]
+    fn joins_letters_across_spans() {
+        let font = load_font(&"./tests/fixtures/DejaVuSansMono.ttf").unwrap();
+        let features = FontFeatures::default();
+        // Beh, beh: an initial form, then a final one.
+        let line = "\u{628}\u{628}";
+        let whole = shape_line(&font, line, &features);
+        let first = shape_span(&font, line, 0..2, &features);
+        let second = shape_span(&font, line, 2..4, &features);
+        let isolated = shape_line(&font, "\u{628}", &features);
+        assert_that(&first[0].glyph_id).is_not_equal_to(isolated[0].glyph_id);
+        assert_that(&vec![second[0].glyph_id, first[0].glyph_id])
+            .is_equal_to(whole.iter().map(|g| g.glyph_id).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn gives_neutral_spans_the_line_direction() {
+        let font = load_font(&"./tests/fixtures/DejaVuSansMono.ttf").unwrap();
+        let line = "שלום, עולם";
+        assert_that(&line_direction(line)).is_equal_to(Direction::RightToLeft);
+        assert_that(&line_direction("fly")).is_equal_to(Direction::LeftToRight);
+        let comma = line.find(',').unwrap();
+        let glyphs = shape_span(&font, line, comma..comma + 2, &FontFeatures::default());
+        let clusters = glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>();
+        assert_that(&clusters).is_equal_to(vec![1, 0]);
+    }
+}
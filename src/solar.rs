@@ -0,0 +1,150 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use crate::color::Color;
+use crate::error::{PoetryWallError, Result};
+
+/// How high the sun's center is at sunrise and sunset, in degrees, allowing
+/// for refraction and the size of the sun.
+const SUNRISE_ELEVATION: f64 = -0.833;
+
+/// How low the sun is at the end of civil twilight, when it's night.
+const DUSK_ELEVATION: f64 = -6.0;
+
+/// A text color on a background, written `COLOR/BACKGROUND`.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub color: Color,
+    pub background: Color,
+}
+
+impl Palette {
+    pub fn new(color: Color, background: Color) -> Self {
+        Palette { color, background }
+    }
+}
+
+impl FromStr for Palette {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (color, background) = s
+            .split_once('/')
+            .ok_or_else(|| PoetryWallError::InvalidMissingOption(format!("palette: {}", s)))?;
+        Ok(Palette::new(color.trim().parse()?, background.trim().parse()?))
+    }
+}
+
+/// Picks a palette by where the sun is in the sky at a place, which is worked
+/// out from the date without going online.
+#[derive(Debug, Clone)]
+pub struct Solar {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub day: Palette,
+    pub night: Palette,
+}
+
+impl Solar {
+    /// The latitude is in degrees north, and the longitude in degrees east.
+    pub fn new(latitude: f64, longitude: f64, day: Palette, night: Palette) -> Result<Self> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(PoetryWallError::InvalidMissingOption(format!("latitude: {}", latitude)));
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(PoetryWallError::InvalidMissingOption(format!("longitude: {}", longitude)));
+        }
+        Ok(Solar { latitude, longitude, day, night })
+    }
+
+    /// The day palette while the sun is up, the night one once civil
+    /// twilight is over, and a blend of the backgrounds in between. Blending
+    /// the text too would leave it gray on gray halfway through, so it's in
+    /// whichever palette's color stands out more.
+    pub fn palette_at(&self, time: DateTime<Utc>) -> Palette {
+        let elevation = sun_elevation(self.latitude, self.longitude, time);
+        let day = ((elevation - DUSK_ELEVATION) / (SUNRISE_ELEVATION - DUSK_ELEVATION)).clamp(0.0, 1.0) as f32;
+        let background = self.day.background.alpha_composite(&self.night.background, day);
+        let contrast = |color: &Color| (luminance(color) - luminance(&background)).abs();
+        let color = if contrast(&self.day.color) >= contrast(&self.night.color) {
+            self.day.color.clone()
+        } else {
+            self.night.color.clone()
+        };
+        Palette::new(color, background)
+    }
+}
+
+/// How light a color looks, from 0 to 1, ignoring gamma.
+fn luminance(color: &Color) -> f32 {
+    let color = color.srgb();
+    (0.2126 * color.red as f32 + 0.7152 * color.green as f32 + 0.0722 * color.blue as f32) / 255.0
+}
+
+/// How high the sun is above the horizon, in degrees, from the low-precision
+/// formulas in the Astronomical Almanac. It's good to about a hundredth of a
+/// degree for the next few decades.
+pub fn sun_elevation(latitude: f64, longitude: f64, time: DateTime<Utc>) -> f64 {
+    let radians = PI / 180.0;
+    // Days since noon on the first of January, 2000.
+    let days = time.timestamp() as f64 / 86_400.0 + 2_440_587.5 - 2_451_545.0;
+
+    let mean_longitude = (280.460 + 0.985_647_4 * days).rem_euclid(360.0);
+    let mean_anomaly = (357.528 + 0.985_600_3 * days).rem_euclid(360.0) * radians;
+    let ecliptic_longitude = (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin()) * radians;
+    let obliquity = (23.439 - 0.000_000_4 * days) * radians;
+
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+    let right_ascension = (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let sidereal_time = (280.460_618_37 + 360.985_647_366_29 * days + longitude).rem_euclid(360.0) * radians;
+    let hour_angle = sidereal_time - right_ascension;
+
+    let latitude = latitude * radians;
+    let elevation = (latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos()).asin();
+    elevation / radians
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+    use spectral::prelude::*;
+
+    use crate::solar::{sun_elevation, Palette, Solar};
+
+    const LONDON: (f64, f64) = (51.5074, -0.1278);
+
+    fn time(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, month, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn finds_the_sun() {
+        // At the solstice, the sun is 90° - 51.5° + 23.4° up at noon.
+        assert_that(&sun_elevation(LONDON.0, LONDON.1, time(6, 21, 12, 2))).is_close_to(61.9, 0.2);
+        assert_that(&sun_elevation(LONDON.0, LONDON.1, time(12, 21, 0, 0))).is_less_than(-60.0);
+        // Sunset was at 20:21 UTC.
+        assert_that(&sun_elevation(LONDON.0, LONDON.1, time(6, 21, 20, 21))).is_close_to(-0.833, 0.2);
+    }
+
+    #[test]
+    fn blends_through_twilight() {
+        let day: Palette = "black/white".parse().unwrap();
+        let night: Palette = "gray/black".parse().unwrap();
+        let solar = Solar::new(LONDON.0, LONDON.1, day, night).unwrap();
+        let colors = |time| {
+            let palette = solar.palette_at(time);
+            (palette.color.as_array(), palette.background.as_array())
+        };
+
+        assert_that(&colors(time(6, 21, 12, 0))).is_equal_to(([0, 0, 0, 255], [255, 255, 255, 255]));
+        assert_that(&colors(time(12, 21, 23, 0))).is_equal_to(([128, 128, 128, 255], [0, 0, 0, 255]));
+        let (text, dusk) = colors(time(6, 21, 20, 45));
+        assert_that(&(dusk[0] > 0 && dusk[0] < 255)).is_true();
+        assert_that(&(text[0] == 0 || text[0] == 128)).is_true();
+
+        assert_that(&"black".parse::<Palette>()).is_err();
+        assert_that(&Solar::new(91.0, 0.0, solar.day.clone(), solar.night.clone())).is_err();
+    }
+}
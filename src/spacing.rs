@@ -0,0 +1,83 @@
+use std::str::FromStr;
+
+use crate::error::PoetryWallError;
+
+/// How far apart to set the lines, measured from baseline to baseline.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LineHeight {
+    /// The font's own ascent, descent and line gap.
+    #[default]
+    Normal,
+    /// A multiple of the em size, like CSS's unitless `line-height`.
+    Multiple(f32),
+    /// A fixed number of pixels at the starting font size. This shrinks along
+    /// with the type when the poem has to be scaled down to fit.
+    Absolute(f32),
+}
+
+impl FromStr for LineHeight {
+    type Err = PoetryWallError;
+
+    /// Reads `normal`, a multiplier like `1.4`, or pixels like `96px`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let error = || PoetryWallError::SpacingReadError(format!("line height '{}'", s));
+        let line_height = if s == "normal" {
+            LineHeight::Normal
+        } else if let Some(pixels) = s.strip_suffix("px") {
+            LineHeight::Absolute(pixels.trim().parse().map_err(|_| error())?)
+        } else {
+            LineHeight::Multiple(s.parse().map_err(|_| error())?)
+        };
+        match line_height {
+            LineHeight::Multiple(value) | LineHeight::Absolute(value)
+                if !(value.is_finite() && value > 0.0) =>
+            {
+                Err(error())
+            }
+            line_height => Ok(line_height),
+        }
+    }
+}
+
+/// The extra space to add between letters, words and lines. Letter and word
+/// spacing are in ems, so they scale with the type.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spacing {
+    pub letter: f32,
+    pub word: f32,
+    pub line_height: LineHeight,
+}
+
+impl Spacing {
+    pub fn new(letter: f32, word: f32, line_height: LineHeight) -> Self {
+        Spacing {
+            letter,
+            word,
+            line_height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use spectral::prelude::*;
+
+    use crate::spacing::LineHeight;
+
+    #[test]
+    fn reads_line_height_from_string() {
+        assert_that(&LineHeight::from_str("normal")).is_ok().is_equal_to(LineHeight::Normal);
+        assert_that(&LineHeight::from_str("1.4")).is_ok().is_equal_to(LineHeight::Multiple(1.4));
+        assert_that(&LineHeight::from_str("96px")).is_ok().is_equal_to(LineHeight::Absolute(96.0));
+    }
+
+    #[test]
+    fn fails_on_invalid_line_height() {
+        assert_that(&LineHeight::from_str("tall")).is_err();
+        assert_that(&LineHeight::from_str("0")).is_err();
+        assert_that(&LineHeight::from_str("-12px")).is_err();
+    }
+}
@@ -0,0 +1,112 @@
+use crate::markup::StyledLine;
+
+/// Whether a line is the blank line between stanzas.
+pub fn is_blank(line: &StyledLine) -> bool {
+    line.iter().all(|span| span.text.trim().is_empty())
+}
+
+/// Splits the lines into at most `count` parts of about the same length.
+/// Each break goes on the blank line between stanzas closest to where it
+/// would fall evenly, if there's one within half a part of it. Otherwise it
+/// goes inside a stanza, but not where it would leave a stanza's first line
+/// alone at the end of a part or its last line alone at the start of the
+/// next. The blank lines around breaks are dropped.
+pub fn split_evenly(lines: &[StyledLine], count: usize) -> Vec<&[StyledLine]> {
+    let count = count.max(1);
+    let part_length = lines.len() as f32 / count as f32;
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for k in 1..count {
+        let ideal = (k as f32 * part_length).round() as usize;
+        let reach = (part_length / 2.0).floor() as usize;
+        let stanza_break = (start + 1..lines.len())
+            .filter(|&i| is_blank(&lines[i]) && i.abs_diff(ideal) <= reach)
+            .min_by_key(|&i| i.abs_diff(ideal));
+        let end = stanza_break
+            .unwrap_or_else(|| avoid_widows(lines, start, ideal.max(start + 1).min(lines.len())));
+        parts.push(&lines[start..end]);
+        start = end;
+        while start < lines.len() && is_blank(&lines[start]) {
+            start += 1;
+        }
+    }
+    parts.push(&lines[start..]);
+
+    parts
+        .into_iter()
+        .map(|part| {
+            let end = part.iter().rposition(|line| !is_blank(line)).map_or(0, |i| i + 1);
+            &part[..end]
+        })
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Moves a break inside a stanza so there are at least two of the stanza's
+/// lines on each side of it, or to the start of the stanza if it's too short
+/// for that. The break never moves back to `start` or before it.
+fn avoid_widows(lines: &[StyledLine], start: usize, end: usize) -> usize {
+    if end >= lines.len() || is_blank(&lines[end]) {
+        return end;
+    }
+    let stanza_start = lines[..end].iter().rposition(is_blank).map_or(0, |i| i + 1);
+    let stanza_end = lines[end..].iter().position(is_blank).map_or(lines.len(), |i| end + i);
+    // Only a stanza of four lines or more has room for two on each side.
+    if stanza_end - stanza_start >= 4 {
+        let end = end.clamp(stanza_start + 2, stanza_end - 2);
+        if end > start {
+            return end;
+        }
+    }
+    if stanza_start > start {
+        stanza_start
+    } else {
+        stanza_end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use crate::markup::{parse_emphasis, StyledLine};
+    use crate::stanza::split_evenly;
+
+    fn lines(text: &str) -> Vec<StyledLine> {
+        text.lines().map(parse_emphasis).collect()
+    }
+
+    fn lengths(text: &str, count: usize) -> Vec<usize> {
+        let poem = lines(text);
+        split_evenly(&poem, count).iter().map(|part| part.len()).collect()
+    }
+
+    #[test]
+    fn breaks_at_stanzas() {
+        assert_that(&lengths("a\nb\nc\n\nd\ne\n\nf\ng\nh", 2)).is_equal_to(vec![6, 3]);
+    }
+
+    #[test]
+    fn breaks_inside_long_stanzas() {
+        assert_that(&lengths("a\nb\nc\nd\ne\nf", 3)).is_equal_to(vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn avoids_orphans() {
+        // An even split would leave "c" alone at the end of the first part.
+        assert_that(&lengths("a\n\nc\nd\ne\nf\ng\nh\ni\nj", 3)).is_equal_to(vec![4, 3, 3]);
+    }
+
+    #[test]
+    fn splits_single_line_stanzas() {
+        assert_that(&lengths("a\n\nb\n\nc", 3)).is_equal_to(vec![1, 1, 1]);
+        assert_that(&lengths("a\n\nb\n\nc\n\nd", 2)).is_equal_to(vec![3, 3]);
+        assert_that(&lengths("a\nb\n\nc\n\nd\ne", 4)).is_equal_to(vec![2, 1, 2]);
+        // Columns and pages can ask for more parts than there are stanzas.
+        let poem = lines("a\n\nb\n\nc");
+        for count in 1..=6 {
+            assert_that(&split_evenly(&poem, count).len()).is_less_than_or_equal_to(count);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::PoetryWallError;
+
+const NARROW_NO_BREAK_SPACE: char = '\u{202F}';
+const NO_BREAK_SPACE: char = '\u{A0}';
+
+/// Words that start with an apostrophe standing in for dropped letters, so
+/// they aren't mistaken for an opening quote.
+const ELISIONS: &[&str] = &["tis", "twas", "twere", "twill", "em", "n", "cause", "til", "neath", "ere"];
+
+/// The language whose quotation conventions to follow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    French,
+    German,
+}
+
+impl Language {
+    /// The opening and closing double quotes, then the single quotes.
+    fn quotes(self) -> (char, char, char, char) {
+        match self {
+            Language::English => ('“', '”', '‘', '’'),
+            Language::French => ('«', '»', '‹', '›'),
+            Language::German => ('„', '“', '‚', '‘'),
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "en" | "english" => Ok(Language::English),
+            "fr" | "french" => Ok(Language::French),
+            "de" | "german" => Ok(Language::German),
+            _ => Err(PoetryWallError::LanguageReadError(String::from(s))),
+        }
+    }
+}
+
+/// One change the typographic pass made. The line and column are 1-based,
+/// and the column counts characters in the original line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Substitution {
+    pub line: usize,
+    pub column: usize,
+    pub from: String,
+    pub to: String,
+}
+
+/// Everything the typographic pass changed in a poem.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypographyReport(Vec<Substitution>);
+
+impl TypographyReport {
+    pub fn substitutions(&self) -> &[Substitution] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for TypographyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for substitution in &self.0 {
+            writeln!(
+                f,
+                "{}:{}: {:?} -> {:?}",
+                substitution.line, substitution.column, substitution.from, substitution.to
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces typewriter punctuation with typographic punctuation: curly
+/// quotes and apostrophes, en and em dashes for `--` and `---`, and ellipses
+/// for `...`. French also gets no-break spaces inside guillemets and before
+/// `;`, `:`, `!` and `?`. Quotes can span lines, so this works on the whole
+/// poem at once. `first_line` is the line number of the first line in the
+/// file, for the report.
+pub fn smarten(lines: &[String], language: Language, first_line: usize) -> (Vec<String>, TypographyReport) {
+    let mut smartener = Smartener {
+        language,
+        single_open: false,
+        report: Vec::new(),
+    };
+    let lines = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| smartener.line(first_line + i, line))
+        .collect();
+    (lines, TypographyReport(smartener.report))
+}
+
+struct Smartener {
+    language: Language,
+    single_open: bool,
+    report: Vec<Substitution>,
+}
+
+impl Smartener {
+    fn line(&mut self, line_number: usize, line: &str) -> String {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut output = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match self.substitute(&chars, i) {
+                Some((consumed, replacement)) => {
+                    self.report.push(Substitution {
+                        line: line_number,
+                        column: i + 1,
+                        from: chars[i..i + consumed].iter().collect(),
+                        to: replacement.clone(),
+                    });
+                    output.push_str(&replacement);
+                    i += consumed;
+                }
+                None => {
+                    output.push(chars[i]);
+                    i += 1;
+                }
+            }
+        }
+        output
+    }
+
+    /// What to replace the text at `i` with, and how many characters that
+    /// replaces.
+    fn substitute(&mut self, chars: &[char], i: usize) -> Option<(usize, String)> {
+        let previous = if i > 0 { Some(chars[i - 1]) } else { None };
+        let next = chars.get(i + 1).cloned();
+        let (open_double, close_double, open_single, close_single) = self.language.quotes();
+        let french = self.language == Language::French;
+
+        match chars[i] {
+            '.' | '-' => {
+                let run = chars[i..].iter().take_while(|&&c| c == chars[i]).count();
+                if previous == Some(chars[i]) {
+                    return None;
+                }
+                match (chars[i], run) {
+                    ('.', 3) => Some((3, String::from("…"))),
+                    ('-', 3) => Some((3, String::from("—"))),
+                    ('-', 2) => Some((2, String::from("–"))),
+                    ('-', 1) if is_space(previous) && previous.is_some() && next == Some(' ') => {
+                        Some((1, String::from("–")))
+                    }
+                    _ => None,
+                }
+            }
+            '"' if is_opening_context(previous) => {
+                if french {
+                    let consumed = if next == Some(' ') { 2 } else { 1 };
+                    Some((consumed, format!("{}{}", open_double, NARROW_NO_BREAK_SPACE)))
+                } else {
+                    Some((1, open_double.to_string()))
+                }
+            }
+            '"' => {
+                if french {
+                    Some((1, format!("{}{}", NARROW_NO_BREAK_SPACE, close_double)))
+                } else {
+                    Some((1, close_double.to_string()))
+                }
+            }
+            '\'' => Some((1, self.single_quote(chars, i, open_single, close_single).to_string())),
+            ' ' if french => {
+                let punctuation = next?;
+                if "!?;»".contains(punctuation) {
+                    Some((1, NARROW_NO_BREAK_SPACE.to_string()))
+                } else if punctuation == ':' && !is_digit(chars.get(i + 2).cloned()) {
+                    Some((1, NO_BREAK_SPACE.to_string()))
+                } else if previous == Some('«') {
+                    Some((1, NARROW_NO_BREAK_SPACE.to_string()))
+                } else {
+                    None
+                }
+            }
+            '!' | '?' | ';' if french && is_word(previous) => {
+                Some((1, format!("{}{}", NARROW_NO_BREAK_SPACE, chars[i])))
+            }
+            ':' if french && is_word(previous) && !is_digit(next) => {
+                Some((1, format!("{}:", NO_BREAK_SPACE)))
+            }
+            _ => None,
+        }
+    }
+
+    fn single_quote(&mut self, chars: &[char], i: usize, open: char, close: char) -> char {
+        let previous = if i > 0 { Some(chars[i - 1]) } else { None };
+        let next = chars.get(i + 1).cloned();
+        if is_word(previous) && is_word(next) {
+            '’'
+        } else if is_opening_context(previous) {
+            let word = chars[i + 1..]
+                .iter()
+                .take_while(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase();
+            if is_digit(next) || ELISIONS.contains(&word.as_str()) {
+                '’'
+            } else {
+                self.single_open = true;
+                open
+            }
+        } else if self.single_open {
+            self.single_open = false;
+            close
+        } else {
+            '’'
+        }
+    }
+}
+
+fn is_space(c: Option<char>) -> bool {
+    c.is_none_or(char::is_whitespace)
+}
+
+fn is_word(c: Option<char>) -> bool {
+    c.is_some_and(char::is_alphanumeric)
+}
+
+fn is_digit(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_ascii_digit())
+}
+
+fn is_opening_context(previous: Option<char>) -> bool {
+    is_space(previous) || previous.is_some_and(|c| "([{“‘«‹„‚–—*_".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use spectral::prelude::*;
+
+    use crate::typography::{smarten, Language};
+
+    fn smarten_one(line: &str, language: Language) -> String {
+        smarten(&[String::from(line)], language, 1).0.remove(0)
+    }
+
+    #[test]
+    fn curls_quotes_and_apostrophes() {
+        assert_that(&smarten_one(r#""I can't," she said, 'not in '99.'"#, Language::English))
+            .is_equal_to(String::from("“I can’t,” she said, ‘not in ’99.’"));
+        assert_that(&smarten_one("'Tis runnin' home", Language::English))
+            .is_equal_to(String::from("’Tis runnin’ home"));
+    }
+
+    #[test]
+    fn uses_language_quotes() {
+        assert_that(&smarten_one(r#"Er sagte "nein""#, Language::German))
+            .is_equal_to(String::from("Er sagte „nein“"));
+        assert_that(&smarten_one(r#"Il dit "non" : pourquoi?"#, Language::French))
+            .is_equal_to(String::from("Il dit «\u{202F}non\u{202F}»\u{A0}: pourquoi\u{202F}?"));
+    }
+
+    #[test]
+    fn replaces_dashes_and_ellipses() {
+        assert_that(&smarten_one("Then -- and then --- the end...", Language::English))
+            .is_equal_to(String::from("Then – and then — the end…"));
+        assert_that(&smarten_one("well-known ----", Language::English))
+            .is_equal_to(String::from("well-known ----"));
+    }
+
+    #[test]
+    fn reports_substitutions() {
+        let lines = vec![String::from("plain"), String::from("a -- b")];
+        let (_, report) = smarten(&lines, Language::English, 1);
+        assert_that(&report.substitutions().len()).is_equal_to(1);
+        assert_that(&report.to_string()).is_equal_to(String::from("2:3: \"--\" -> \"–\"\n"));
+    }
+}
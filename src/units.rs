@@ -0,0 +1,117 @@
+use std::str::FromStr;
+
+use crate::error::{PoetryWallError, Result};
+
+/// The resolution pixels are assumed to have when there isn't a `--dpi`.
+pub const DEFAULT_DPI: f32 = 96.0;
+
+const POINTS_PER_INCH: f32 = 72.0;
+const MILLIMETERS_PER_INCH: f32 = 25.4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Px,
+    Pt,
+    Mm,
+    In,
+    /// The size of the type.
+    Em,
+    /// A percentage of the image's width or height.
+    Percent,
+}
+
+/// A distance in one of the units the options take. Plain numbers are
+/// pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Length {
+    pub value: f32,
+    pub unit: Unit,
+}
+
+impl Length {
+    pub fn new(value: f32, unit: Unit) -> Self {
+        Length { value, unit }
+    }
+
+    pub fn px(value: f32) -> Self {
+        Length::new(value, Unit::Px)
+    }
+
+    /// The length in pixels at `dpi`. Ems are of `em` pixels, and percentages
+    /// are of `reference` pixels.
+    pub fn to_pixels(self, dpi: f32, em: f32, reference: f32) -> f32 {
+        match self.unit {
+            Unit::Px => self.value,
+            Unit::Pt => self.value * dpi / POINTS_PER_INCH,
+            Unit::Mm => self.value * dpi / MILLIMETERS_PER_INCH,
+            Unit::In => self.value * dpi,
+            Unit::Em => self.value * em,
+            Unit::Percent => self.value * reference / 100.0,
+        }
+    }
+
+    /// The length in pixels for something that can't be relative to the type,
+    /// like the size of the type itself.
+    pub fn to_absolute_pixels(self, dpi: f32, reference: f32) -> Result<f32> {
+        match self.unit {
+            Unit::Em => Err(PoetryWallError::LengthReadError(format!(
+                "{}em can't be used here",
+                self.value
+            ))),
+            _ => Ok(self.to_pixels(dpi, 0.0, reference)),
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = PoetryWallError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let unit = match unit.trim() {
+            "" | "px" => Unit::Px,
+            "pt" => Unit::Pt,
+            "mm" => Unit::Mm,
+            "in" => Unit::In,
+            "em" => Unit::Em,
+            "%" => Unit::Percent,
+            _ => return Err(PoetryWallError::LengthReadError(String::from(s))),
+        };
+        number
+            .parse()
+            .map(|value| Length::new(value, unit))
+            .map_err(|_| PoetryWallError::LengthReadError(String::from(s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use spectral::prelude::*;
+
+    use crate::units::{Length, Unit};
+
+    #[test]
+    fn reads_from_string() {
+        assert_that(&Length::from_str("12")).is_ok().is_equal_to(Length::px(12.0));
+        assert_that(&Length::from_str("8.5in")).is_ok().is_equal_to(Length::new(8.5, Unit::In));
+        assert_that(&Length::from_str("-2em")).is_ok().is_equal_to(Length::new(-2.0, Unit::Em));
+        assert_that(&Length::from_str("10%")).is_ok().is_equal_to(Length::new(10.0, Unit::Percent));
+        assert_that(&Length::from_str("12furlongs")).is_err();
+        assert_that(&Length::from_str("pt")).is_err();
+    }
+
+    #[test]
+    fn converts_to_pixels() {
+        assert_that(&Length::new(72.0, Unit::Pt).to_pixels(300.0, 0.0, 0.0)).is_close_to(300.0, 0.001);
+        assert_that(&Length::new(25.4, Unit::Mm).to_pixels(150.0, 0.0, 0.0)).is_close_to(150.0, 0.001);
+        assert_that(&Length::new(1.5, Unit::Em).to_pixels(96.0, 40.0, 0.0)).is_close_to(60.0, 0.001);
+        assert_that(&Length::new(25.0, Unit::Percent).to_pixels(96.0, 0.0, 1080.0)).is_close_to(270.0, 0.001);
+        assert_that(&Length::new(1.0, Unit::Em).to_absolute_pixels(96.0, 0.0)).is_err();
+    }
+}
@@ -0,0 +1,328 @@
+use ab_glyph::PxScale;
+
+use crate::alignment::Alignment;
+use crate::color::Color;
+use crate::columns::Columns;
+use crate::dimension::Dimension;
+use crate::error::{PoetryWallError, Result};
+use crate::font::{load_font, Font, FontFamily, FontVariations};
+use crate::image_utils::{create_image, draw_rules, render_glyphs, Image};
+use crate::layout::LayoutPlan;
+use crate::metrics::Metrics;
+use crate::options::PoetryWallOptions;
+use crate::overlay::draw_overlay;
+use crate::placement::VerticalPlacement;
+use crate::poem::Poem;
+use crate::shaping::FontFeatures;
+use crate::spacing::Spacing;
+use crate::typography::Language;
+use crate::units::Length;
+
+/// A poem and font ready to be laid out and rendered in memory, at as many
+/// sizes as needed.
+pub struct PoetryWall {
+    poem: Poem,
+    /// This is only missing while it's lent to the metrics.
+    family: Option<FontFamily>,
+    options: PoetryWallOptions,
+}
+
+impl PoetryWall {
+    /// Smartens the poem's typography if the options or its front matter ask
+    /// for it. The front matter wins over the options, unless they were on the
+    /// command line. A solar palette is picked now, and kept for every size.
+    pub fn new(mut poem: Poem, mut family: FontFamily, mut options: PoetryWallOptions) -> Result<Self> {
+        let front_matter = poem.front_matter();
+        let typography = match front_matter.typography()? {
+            Some(typography) if !options.command_line.contains("smart-typography") => typography,
+            _ => options.typography,
+        };
+        let language = match front_matter.language()? {
+            Some(language) if !options.command_line.contains("lang") => language,
+            _ => options.language,
+        };
+        if typography {
+            let report = poem.smarten(language);
+            if options.typography_report {
+                eprint!("{}", report);
+            }
+        }
+        family.set_variations(&options.variations)?;
+        options.resolve_palette();
+        Ok(PoetryWall { poem, family: Some(family), options })
+    }
+
+    pub fn builder() -> PoetryWallBuilder {
+        PoetryWallBuilder::default()
+    }
+
+    pub fn options(&self) -> &PoetryWallOptions {
+        &self.options
+    }
+
+    /// Lays out the poem at one size.
+    pub fn plan(&mut self, dimensions: Dimension) -> LayoutPlan {
+        let family = self.family.take().expect("the font family is always returned");
+        let mut metrics = Metrics::compute_metrics(&self.options, dimensions, &self.poem, family);
+        if self.options.debug_overlay {
+            eprintln!(
+                "{}x{}: font size {:.2}px after {} fit iterations; left margin {:.0}px, top margin {:.0}px",
+                dimensions.width,
+                dimensions.height,
+                metrics.font_size,
+                metrics.fit_iterations,
+                metrics.left_offset,
+                metrics.top_offset
+            );
+        }
+        let plan = metrics.layout_plan(&self.options);
+        self.family = Some(metrics.family);
+        plan
+    }
+
+    /// Draws each page of a plan made by `plan`.
+    pub fn draw(&mut self, plan: &LayoutPlan) -> Vec<Image> {
+        let family = self.family.as_mut().expect("the font family is always returned");
+        draw_layout_plan(plan, family, &self.options)
+    }
+
+    /// Renders every page at the first of the options' sizes. There's only one
+    /// unless the poem is split up to stay above the minimum font size.
+    pub fn render_pages(&mut self) -> Vec<Image> {
+        let dimensions = self.options.dimensions.first().copied().unwrap_or_else(|| Dimension::new(1920, 1080));
+        let plan = self.plan(dimensions);
+        self.draw(&plan)
+    }
+
+    /// Renders the first page at the first of the options' sizes.
+    pub fn render(&mut self) -> Image {
+        self.render_pages().swap_remove(0)
+    }
+}
+
+/// Builds a `PoetryWall` from a poem and font that are already in memory.
+/// Anything not set has the same default as on the command line, and the
+/// font defaults to the built-in serif.
+#[derive(Default)]
+pub struct PoetryWallBuilder {
+    poem: Option<Poem>,
+    family: Option<FontFamily>,
+    options: PoetryWallOptions,
+}
+
+impl PoetryWallBuilder {
+    pub fn poem(mut self, poem: Poem) -> Self {
+        self.poem = Some(poem);
+        self
+    }
+
+    /// The font to set the poem in, with bold and italic synthesized from it.
+    pub fn font(mut self, font: Font) -> Self {
+        self.family = Some(FontFamily::new(font));
+        self
+    }
+
+    pub fn family(mut self, family: FontFamily) -> Self {
+        self.family = Some(family);
+        self
+    }
+
+    /// Starts over from these options, keeping the poem and font.
+    pub fn options(mut self, options: PoetryWallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn dimensions(mut self, dimensions: Dimension) -> Self {
+        self.options.dimensions = vec![dimensions];
+        self
+    }
+
+    pub fn font_size(mut self, font_size: Length) -> Self {
+        self.options.font_size = font_size;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.options.color = color;
+        self
+    }
+
+    pub fn background(mut self, background: Color) -> Self {
+        self.options.background = background;
+        self
+    }
+
+    pub fn top(mut self, top: Length) -> Self {
+        self.options.top = Some(top);
+        self
+    }
+
+    pub fn left(mut self, left: Length) -> Self {
+        self.options.left = Some(left);
+        self
+    }
+
+    pub fn features(mut self, features: FontFeatures) -> Self {
+        self.options.features = features;
+        self
+    }
+
+    pub fn variations(mut self, variations: FontVariations) -> Self {
+        self.options.variations = variations;
+        self
+    }
+
+    pub fn spacing(mut self, spacing: Spacing) -> Self {
+        self.options.spacing = spacing;
+        self
+    }
+
+    pub fn smart_typography(mut self, typography: bool) -> Self {
+        self.options.typography = typography;
+        self
+    }
+
+    pub fn language(mut self, language: Language) -> Self {
+        self.options.language = language;
+        self
+    }
+
+    pub fn alignment(mut self, alignment: Alignment) -> Self {
+        self.options.alignment = alignment;
+        self
+    }
+
+    pub fn hanging_punctuation(mut self, hanging_punctuation: f32) -> Self {
+        self.options.hanging_punctuation = hanging_punctuation;
+        self
+    }
+
+    pub fn vertical_placement(mut self, vertical_placement: VerticalPlacement) -> Self {
+        self.options.vertical_placement = vertical_placement;
+        self
+    }
+
+    pub fn baseline_grid(mut self, grid: f32) -> Self {
+        self.options.baseline_grid = Some(grid);
+        self
+    }
+
+    pub fn columns(mut self, columns: Columns) -> Self {
+        self.options.columns = columns;
+        self
+    }
+
+    pub fn column_gap(mut self, column_gap: f32) -> Self {
+        self.options.column_gap = column_gap;
+        self
+    }
+
+    pub fn column_rule(mut self, width: f32) -> Self {
+        self.options.column_rule = Some(width);
+        self
+    }
+
+    pub fn min_font_size(mut self, min_font_size: Length) -> Self {
+        self.options.min_font_size = Some(min_font_size);
+        self
+    }
+
+    pub fn continued_marker(mut self, marker: &str) -> Self {
+        self.options.continued_marker = Some(String::from(marker));
+        self
+    }
+
+    pub fn page_numbers(mut self, page_numbers: bool) -> Self {
+        self.options.page_numbers = page_numbers;
+        self
+    }
+
+    pub fn debug_overlay(mut self, debug_overlay: bool) -> Self {
+        self.options.debug_overlay = debug_overlay;
+        self
+    }
+
+    pub fn dpi(mut self, dpi: f32) -> Self {
+        self.options.dpi = Some(dpi);
+        self
+    }
+
+    pub fn build(self) -> Result<PoetryWall> {
+        let poem = self
+            .poem
+            .ok_or_else(|| PoetryWallError::InvalidMissingOption(String::from("poem")))?;
+        let family = match self.family {
+            Some(family) => family,
+            None => load_family(&self.options, &poem)?,
+        };
+        PoetryWall::new(poem, family, self.options)
+    }
+}
+
+/// Loads the options' font, and its other styles if the poem needs them.
+pub(crate) fn load_family(options: &PoetryWallOptions, poem: &Poem) -> Result<FontFamily> {
+    if poem.has_emphasis() || options.continued_marker.is_some() {
+        FontFamily::discover(&options.font_file)
+    } else {
+        Ok(FontFamily::new(load_font(&options.font_file)?))
+    }
+}
+
+/// Draws each page of a plan in the colors from the options.
+pub(crate) fn draw_layout_plan(plan: &LayoutPlan, family: &mut FontFamily, options: &PoetryWallOptions) -> Vec<Image> {
+    let background = options.background.srgb();
+
+    let mut images = Vec::new();
+    for page in &plan.pages {
+        let mut image = create_image(plan.width, plan.height, background.red, background.green, background.blue);
+        if let Some(width) = options.column_rule {
+            draw_rules(&mut image, &page.column_rules, &page.line_box, width, &options.color);
+        }
+        for line in &page.lines {
+            if plan.auto_optical_size {
+                family.set_optical_size(PxScale::from(line.size));
+            }
+            render_glyphs(&mut image, &line.positioned_glyphs(family), &options.color);
+        }
+        if options.debug_overlay {
+            draw_overlay(&mut image, plan, page, family);
+        }
+        images.push(image);
+    }
+
+    images
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use spectral::prelude::*;
+
+    use crate::dimension::Dimension;
+    use crate::font::Font;
+    use crate::poem::Poem;
+    use crate::units::Length;
+    use crate::wall::PoetryWall;
+
+    #[test]
+    fn renders_in_memory() {
+        let font = Font::from_bytes(include_bytes!("../tests/fixtures/DejaVuSansMono.ttf").to_vec()).unwrap();
+        let mut wall = PoetryWall::builder()
+            .poem(Poem::from_str("I heard a Fly buzz - when I died -\n").unwrap())
+            .font(font)
+            .dimensions(Dimension::new(320, 240))
+            .font_size(Length::px(24.0))
+            .build()
+            .unwrap();
+        let image = wall.render();
+        assert_that(&image.dimensions()).is_equal_to((320, 240));
+        assert_that(&image.pixels().any(|pixel| pixel.data[0] > 0)).is_true();
+    }
+
+    #[test]
+    fn needs_a_poem() {
+        assert_that(&PoetryWall::builder().build().is_err()).is_true();
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use notify_debouncer_mini::notify::RecursiveMode;
+use notify_debouncer_mini::new_debouncer;
+
+use crate::builtin::builtin_name;
+use crate::config::Config;
+use crate::error::{PoetryWallError, Result};
+use crate::font::sibling_fonts;
+use crate::options::{PoetryWallOptions, Source};
+
+/// How long to wait for a burst of saves to finish before rendering.
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The files a render depends on: the poem, with its front matter, the font
+/// and the faces found next to it, the layout plan and the configuration.
+pub fn watched_files(options: &PoetryWallOptions) -> Vec<PathBuf> {
+    let mut files = Config::paths();
+    if let Some(Source::File(poem_file)) = &options.poem {
+        files.push(poem_file.clone());
+    }
+    if builtin_name(&options.font_file).is_none() {
+        files.push(options.font_file.clone());
+        files.extend(sibling_fonts(&options.font_file));
+    }
+    files.extend(options.layout_file.clone());
+    files
+}
+
+/// Calls `render` now, and again each time one of the files it returns
+/// changes. This only returns if watching fails, so `render` should report
+/// its own errors and carry on.
+pub fn watch<F: FnMut() -> Vec<PathBuf>>(mut render: F) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE, sender).map_err(watch_error)?;
+    let mut directories = BTreeSet::new();
+
+    loop {
+        let files: BTreeSet<PathBuf> = render().iter().map(|file| absolute(file)).collect();
+        // Editors often save by replacing the file, so watch the directories.
+        for directory in files.iter().filter_map(|file| file.parent()) {
+            if directory.is_dir() && directories.insert(directory.to_path_buf()) {
+                debouncer.watcher().watch(directory, RecursiveMode::NonRecursive).map_err(watch_error)?;
+            }
+        }
+
+        // Rendering reads the files, which is an event too, so only go again
+        // once one of them is really different.
+        let rendered = modification_times(&files);
+        loop {
+            let events = receiver.recv().map_err(watch_error)?.map_err(watch_error)?;
+            if events.iter().any(|event| files.contains(&absolute(&event.path)))
+                && modification_times(&files) != rendered
+            {
+                break;
+            }
+        }
+    }
+}
+
+/// When each file was last changed, or `None` if it's missing.
+fn modification_times(files: &BTreeSet<PathBuf>) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+/// The path from the root, following links in the directory but not the file.
+fn absolute(path: &Path) -> PathBuf {
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let directory = directory
+        .canonicalize()
+        .or_else(|_| env::current_dir().map(|cwd| cwd.join(&directory)))
+        .unwrap_or(directory);
+    directory.join(path.file_name().unwrap_or_default())
+}
+
+fn watch_error<E: ToString>(err: E) -> PoetryWallError {
+    PoetryWallError::WatchError(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::{Path, PathBuf};
+
+    use spectral::prelude::*;
+
+    use crate::options::{PoetryWallOptions, Source};
+    use crate::watch::{absolute, watched_files};
+
+    #[test]
+    fn watches_the_poem_and_font() {
+        let mut options = PoetryWallOptions {
+            poem: Some(Source::File(PathBuf::from("tests/fixtures/fly-buzz.md"))),
+            font_file: PathBuf::from("tests/fixtures/DejaVuSansMono.ttf"),
+            ..PoetryWallOptions::default()
+        };
+        let files = watched_files(&options);
+        assert_that(&files.contains(&PathBuf::from("tests/fixtures/fly-buzz.md"))).is_true();
+        assert_that(&files.contains(&options.font_file)).is_true();
+
+        options.font_file = PathBuf::from("builtin:serif");
+        options.poem = Some(Source::Stdin);
+        assert_that(&watched_files(&options).iter().any(|file| file.ends_with("poetry-wall.toml"))).is_true();
+        assert_that(&watched_files(&options).contains(&PathBuf::from("-"))).is_false();
+    }
+
+    #[test]
+    fn resolves_relative_paths() {
+        let cwd = env::current_dir().unwrap().canonicalize().unwrap();
+        assert_that(&absolute(Path::new("wall.md"))).is_equal_to(cwd.join("wall.md"));
+        assert_that(&absolute(Path::new("tests/wall.md"))).is_equal_to(cwd.join("tests").join("wall.md"));
+    }
+}
@@ -29,9 +29,244 @@ fn test_basic_interaction() {
         .arg("--top")
         .arg("100")
         .arg("--output")
-        .arg(&output_file.path())
+        .arg(output_file.path())
         .unwrap();
 
     assert_that(&cmd.status.success()).is_true();
     output_file.assert(predicate::path::exists());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_builtin_font() {
+    let output_dir = assert_fs::TempDir::new().unwrap();
+    let output_file = output_dir.child("fly-buzz.png");
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("--poem")
+        .arg("./tests/fixtures/fly-buzz.md")
+        .arg("--output")
+        .arg(output_file.path())
+        .unwrap();
+
+    assert_that(&cmd.status.success()).is_true();
+    output_file.assert(predicate::path::exists());
+}
+
+#[test]
+fn test_layout_round_trip() {
+    let output_dir = assert_fs::TempDir::new().unwrap();
+    let layout_file = output_dir.child("fly-buzz.json");
+    let direct_file = output_dir.child("direct.png");
+    let planned_file = output_dir.child("planned.png");
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("--poem")
+        .arg("./tests/fixtures/fly-buzz.md")
+        .arg("--font")
+        .arg("./tests/fixtures/DejaVuSansMono.ttf")
+        .arg("--emit-layout")
+        .arg(layout_file.path())
+        .arg("--output")
+        .arg(direct_file.path())
+        .unwrap();
+    assert_that(&cmd.status.success()).is_true();
+    layout_file.assert(predicate::str::contains("\"glyphs\""));
+
+    // The plan works from another directory.
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .current_dir(output_dir.path())
+        .arg("--layout")
+        .arg(layout_file.path())
+        .arg("--output")
+        .arg(planned_file.path())
+        .unwrap();
+    assert_that(&cmd.status.success()).is_true();
+
+    let direct = std::fs::read(direct_file.path()).unwrap();
+    let planned = std::fs::read(planned_file.path()).unwrap();
+    assert_that(&(direct == planned)).is_true();
+}
+
+#[test]
+fn test_many_dimensions() {
+    let output_dir = assert_fs::TempDir::new().unwrap();
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("--poem")
+        .arg("./tests/fixtures/fly-buzz.md")
+        .arg("--dimensions")
+        .arg("640x480,480x640")
+        .arg("--max-font-size")
+        .arg("5%")
+        .arg("--output")
+        .arg(output_dir.child("wall.png").path())
+        .unwrap();
+
+    assert_that(&cmd.status.success()).is_true();
+    output_dir.child("wall-640x480.png").assert(predicate::path::exists());
+    output_dir.child("wall-480x640.png").assert(predicate::path::exists());
+}
+
+#[test]
+fn test_batch() {
+    let output_dir = assert_fs::TempDir::new().unwrap();
+    let poem_dir = output_dir.child("poems");
+    poem_dir.create_dir_all().unwrap();
+    poem_dir.child("fly-buzz.md").write_file(std::path::Path::new("./tests/fixtures/fly-buzz.md")).unwrap();
+    poem_dir.child("bad.md").write_str("---\ntypography: maybe\n---\nbad\n").unwrap();
+    let walls = output_dir.child("walls");
+
+    let batch = || {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg("--dimensions")
+            .arg("640x480")
+            .arg("batch")
+            .arg(poem_dir.path())
+            .arg("--output-dir")
+            .arg(walls.path())
+            .output()
+            .unwrap()
+    };
+
+    let output = batch();
+    assert_that(&output.status.success()).is_false();
+    assert_that(&String::from_utf8_lossy(&output.stdout).into_owned())
+        .contains("1 rendered, 0 up to date, 1 failed");
+    walls.child("fly-buzz.png").assert(predicate::path::exists());
+
+    let output = batch();
+    assert_that(&String::from_utf8_lossy(&output.stdout).into_owned())
+        .contains("0 rendered, 1 up to date, 1 failed");
+}
+
+#[test]
+fn test_batch_format() {
+    let output_dir = assert_fs::TempDir::new().unwrap();
+    let walls = output_dir.child("walls");
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("--dimensions")
+        .arg("640x480")
+        .arg("--format")
+        .arg("jpeg")
+        .arg("batch")
+        .arg("./tests/fixtures/fly-buzz.md")
+        .arg("--output-dir")
+        .arg(walls.path())
+        .output()
+        .unwrap();
+
+    assert_that(&cmd.status.success()).is_true();
+    let image = std::fs::read(walls.child("fly-buzz.jpg").path()).unwrap();
+    assert_that(&image.starts_with(&[0xff, 0xd8, 0xff])).is_true();
+}
+
+#[test]
+fn test_config_profiles() {
+    let config_dir = assert_fs::TempDir::new().unwrap();
+    config_dir
+        .child("poetry-wall.toml")
+        .write_str("color = \"gray\"\nmax-font-size = 48\n\n[profiles.night-phone]\ndimensions = \"1080x2400\"\nmax-font-size = \"5%\"\n")
+        .unwrap();
+
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .current_dir(config_dir.path())
+        // Keep the real user configuration out of it.
+        .env("HOME", config_dir.path())
+        .env("XDG_CONFIG_HOME", config_dir.path())
+        .arg("--profile")
+        .arg("night-phone")
+        .arg("--color")
+        .arg("white")
+        .arg("--print-config")
+        .output()
+        .unwrap();
+    let printed = String::from_utf8_lossy(&output.stdout).into_owned();
+
+    assert_that(&output.status.success()).is_true();
+    assert_that(&printed).contains("dimensions = \"1080x2400\"");
+    assert_that(&printed).contains("max-font-size = \"5%\"");
+    assert_that(&printed).contains("color = \"white\"");
+}
+
+#[test]
+fn test_stdin_to_stdout() {
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("--poem")
+        .arg("-")
+        .arg("--output")
+        .arg("-")
+        .arg("--format")
+        .arg("png")
+        .arg("--dimensions")
+        .arg("320x240")
+        .with_stdin()
+        .buffer("Roses are red,\nviolets are blue.\n")
+        .output()
+        .unwrap();
+
+    assert_that(&output.status.success()).is_true();
+    assert_that(&&output.stdout[..8]).is_equal_to(&b"\x89PNG\r\n\x1a\n"[..]);
+
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("--poem")
+        .arg("-")
+        .arg("--output")
+        .arg("-")
+        .with_stdin()
+        .buffer("Roses are red,\n")
+        .output()
+        .unwrap();
+
+    assert_that(&output.status.success()).is_false();
+    assert_that(&output.stdout.is_empty()).is_true();
+}
+
+#[test]
+fn test_watch_needs_a_poem_file() {
+    let watch = |args: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg("--watch")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = watch(&["--poem", "-", "--output", "wall.png"]);
+    assert_that(&output.status.success()).is_false();
+    assert_that(&String::from_utf8_lossy(&output.stderr).into_owned()).contains("stdin can only be read once");
+
+    let output = watch(&["batch", "./tests/fixtures/fly-buzz.md", "--output-dir", "walls"]);
+    assert_that(&output.status.success()).is_false();
+    assert_that(&String::from_utf8_lossy(&output.stderr).into_owned()).contains("not with batch");
+}
+
+#[test]
+fn test_preview() {
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("--poem")
+        .arg("./tests/fixtures/fly-buzz.md")
+        .arg("--preview")
+        .arg("--preview-protocol")
+        .arg("blocks")
+        .env("COLUMNS", "60")
+        .env("LINES", "16")
+        .output()
+        .unwrap();
+
+    assert_that(&output.status.success()).is_true();
+    let preview = String::from_utf8(output.stdout).unwrap();
+    assert_that(&preview.lines().count()).is_equal_to(15);
+    assert_that(&preview.contains('▀')).is_true();
+}